@@ -0,0 +1,43 @@
+//! examples/big-capacity.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_semihosting as _;
+
+#[rtic::app(device = lm3s6965, dispatchers = [SSI0])]
+mod app {
+    use cortex_m_semihosting::{debug, hprintln};
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(_: init::Context) -> (Shared, Local, init::Monotonics) {
+        // More than 255 entries, the slots are indexed with a `u16`
+        for entry in 0..512 {
+            log::spawn(entry).unwrap();
+        }
+
+        assert!(log::spawn(512).is_err()); // The capacity of `log` is reached
+        hprintln!("log is full").unwrap();
+
+        (Shared {}, Local {}, init::Monotonics())
+    }
+
+    #[task(capacity = 512, local = [sum: u32 = 0])]
+    fn log(cx: log::Context, entry: u32) {
+        *cx.local.sum += entry;
+
+        if entry == 511 {
+            hprintln!("logged {} entries, sum = {}", entry + 1, cx.local.sum).unwrap();
+
+            debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+        }
+    }
+}
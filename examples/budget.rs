@@ -0,0 +1,51 @@
+//! examples/budget.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_semihosting as _;
+
+#[rtic::app(device = lm3s6965, dispatchers = [SSI0])]
+mod app {
+    use cortex_m_semihosting::{debug, hprintln};
+    use rtic::time::duration::*;
+    use systick_monotonic::Systick;
+
+    // Budgets are measured with the default monotonic; its handler runs at the highest priority
+    #[monotonic(binds = SysTick, default = true)]
+    type MyMono = Systick<100>; // 100 Hz / 10 ms granularity
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+        let mono = Systick::new(cx.core.SYST, 12_000_000);
+
+        work::spawn().unwrap();
+
+        (Shared {}, Local {}, init::Monotonics(mono))
+    }
+
+    #[task(budget = 20.milliseconds())]
+    fn work(_: work::Context) {
+        // Takes 100 ms, way over budget
+        let start = monotonics::now();
+        while monotonics::now() < start + 100.milliseconds() {}
+
+        hprintln!("work done").unwrap();
+    }
+
+    // Called while the task that overran is still running
+    #[budget_overrun]
+    fn budget_overrun(task: Task) {
+        hprintln!("budget overrun: {:?}", task).unwrap();
+
+        debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+    }
+}
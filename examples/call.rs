@@ -0,0 +1,47 @@
+//! examples/call.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_semihosting as _;
+
+#[rtic::app(device = lm3s6965, dispatchers = [SSI0, QEI0])]
+mod app {
+    use cortex_m_semihosting::{debug, hprintln};
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(_: init::Context) -> (Shared, Local, init::Monotonics) {
+        handler::spawn().unwrap();
+
+        (Shared {}, Local {}, init::Monotonics())
+    }
+
+    #[task]
+    fn handler(_: handler::Context) {
+        // The reply of `storage` is spawned as `response`; passing a task that takes anything
+        // other than a `u32` is a compile error
+        storage::call(7, response::spawn).unwrap();
+    }
+
+    #[task(priority = 2, local = [values: [u32; 8] = [0, 1, 4, 9, 16, 25, 36, 49]])]
+    fn storage(cx: storage::Context, key: usize) -> u32 {
+        hprintln!("storage: read key {}", key).unwrap();
+
+        cx.local.values[key]
+    }
+
+    #[task]
+    fn response(_: response::Context, value: u32) {
+        hprintln!("response: {}", value).unwrap();
+
+        debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+    }
+}
@@ -0,0 +1,58 @@
+//! examples/const-init.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_semihosting as _;
+
+#[rtic::app(device = lm3s6965, dispatchers = [SSI0])]
+mod app {
+    use cortex_m_semihosting::{debug, hprintln};
+
+    #[shared]
+    struct Shared {
+        // Initialized at compile time, `init` doesn't return it
+        #[init(0)]
+        counter: u32,
+        // Initialized by `init` as usual
+        message: &'static str,
+    }
+
+    #[local]
+    struct Local {
+        #[init([0; 4])]
+        history: [u32; 4],
+    }
+
+    #[init]
+    fn init(_: init::Context) -> (Shared, Local, init::Monotonics) {
+        foo::spawn().unwrap();
+        foo::spawn().unwrap();
+
+        (Shared { message: "counter" }, Local {}, init::Monotonics())
+    }
+
+    #[task(capacity = 2, shared = [counter, message], local = [history])]
+    fn foo(mut cx: foo::Context) {
+        let counter = cx.shared.counter.lock(|counter| {
+            *counter += 1;
+            *counter
+        });
+        cx.local.history[counter as usize] = counter;
+
+        let message = cx.shared.message.lock(|message| *message);
+        hprintln!(
+            "{} = {}, history = {:?}",
+            message,
+            counter,
+            cx.local.history
+        )
+        .unwrap();
+
+        if counter == 2 {
+            debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+        }
+    }
+}
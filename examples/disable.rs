@@ -0,0 +1,48 @@
+//! examples/disable.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_semihosting as _;
+
+#[rtic::app(device = lm3s6965)]
+mod app {
+    use cortex_m_semihosting::{debug, hprintln};
+    use lm3s6965::Interrupt;
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(_: init::Context) -> (Shared, Local, init::Monotonics) {
+        rtic::pend(Interrupt::UART0);
+
+        (Shared {}, Local {}, init::Monotonics())
+    }
+
+    #[task(binds = UART0)]
+    fn foo(_: foo::Context) {
+        // Pause `bar`; it stays pending while disabled
+        bar::disable();
+        rtic::pend(Interrupt::UART1);
+
+        hprintln!("foo: bar enabled = {}", bar::is_enabled()).unwrap();
+
+        // `bar` has a higher priority so it runs right away
+        bar::enable();
+
+        hprintln!("foo: done").unwrap();
+
+        debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+    }
+
+    #[task(binds = UART1, priority = 2)]
+    fn bar(_: bar::Context) {
+        hprintln!("bar").unwrap();
+    }
+}
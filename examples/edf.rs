@@ -0,0 +1,49 @@
+//! examples/edf.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_semihosting as _;
+
+// `SSI0` runs the ready tasks in order of their absolute deadline instead of spawn order
+#[rtic::app(device = lm3s6965, dispatchers = [SSI0 = edf])]
+mod app {
+    use cortex_m_semihosting::{debug, hprintln};
+    use rtic::time::duration::*;
+    use systick_monotonic::Systick;
+
+    // Deadlines are measured with the default monotonic
+    #[monotonic(binds = SysTick, default = true)]
+    type MyMono = Systick<100>; // 100 Hz / 10 ms granularity
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+        let mono = Systick::new(cx.core.SYST, 12_000_000);
+
+        slow::spawn().unwrap();
+        fast::spawn().unwrap();
+
+        (Shared {}, Local {}, init::Monotonics(mono))
+    }
+
+    #[task(deadline = 100.milliseconds())]
+    fn slow(_: slow::Context) {
+        hprintln!("slow").unwrap();
+
+        debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+    }
+
+    // Spawned last, but its deadline is the earliest
+    #[task(deadline = 10.milliseconds())]
+    fn fast(_: fast::Context) {
+        hprintln!("fast").unwrap();
+    }
+}
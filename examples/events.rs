@@ -0,0 +1,49 @@
+//! examples/events.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_semihosting as _;
+
+#[rtic::app(device = lm3s6965, dispatchers = [SSI0])]
+mod app {
+    use cortex_m_semihosting::{debug, hprintln};
+    use lm3s6965::Interrupt;
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    /// A button was pressed, the message is the number of the button
+    #[event]
+    type ButtonPressed = u8;
+
+    #[init]
+    fn init(_: init::Context) -> (Shared, Local, init::Monotonics) {
+        rtic::pend(Interrupt::GPIOA);
+
+        (Shared {}, Local {}, init::Monotonics())
+    }
+
+    #[task(binds = GPIOA)]
+    fn gpioa(_: gpioa::Context) {
+        // The producer doesn't know who the subscribers are
+        ButtonPressed::publish(3).unwrap();
+    }
+
+    #[task(subscribe = [ButtonPressed])]
+    fn display(_: display::Context, button: u8) {
+        hprintln!("display: button {}", button).unwrap();
+    }
+
+    #[task(subscribe = [ButtonPressed])]
+    fn logger(_: logger::Context, button: ButtonPressed::Message) {
+        hprintln!("logger: button {}", button).unwrap();
+
+        debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+    }
+}
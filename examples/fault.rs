@@ -0,0 +1,57 @@
+//! examples/fault.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+// No panic handler crate: RTIC routes panics to the `#[fault]` function
+
+#[rtic::app(device = lm3s6965, dispatchers = [SSI0])]
+mod app {
+    use cortex_m_semihosting::{debug, hprintln};
+    use lm3s6965::Interrupt;
+    use rtic::FaultInfo;
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(_: init::Context) -> (Shared, Local, init::Monotonics) {
+        foo::spawn().unwrap();
+
+        (Shared {}, Local {}, init::Monotonics())
+    }
+
+    #[task]
+    fn foo(_: foo::Context) {
+        hprintln!("current task: {}", rtic::current_task().unwrap().name).unwrap();
+
+        // `uart0` preempts `foo`
+        rtic::pend(Interrupt::UART0);
+    }
+
+    #[task(binds = UART0, priority = 2)]
+    fn uart0(_: uart0::Context) {
+        panic!("uart0 failed");
+    }
+
+    // Called from the panic handler and from the `HardFault` handler
+    #[fault]
+    fn on_fault(info: FaultInfo) {
+        if info.panic.is_some() {
+            hprintln!("panic in:").unwrap();
+        } else {
+            hprintln!("hard fault in:").unwrap();
+        }
+
+        for task in info.chain {
+            hprintln!("  {} (priority {})", task.name, task.priority).unwrap();
+        }
+
+        debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+    }
+}
@@ -0,0 +1,53 @@
+//! examples/frozen.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_semihosting as _;
+
+#[rtic::app(device = lm3s6965)]
+mod app {
+    use cortex_m_semihosting::{debug, hprintln};
+    use lm3s6965::Interrupt;
+
+    #[shared]
+    struct Shared {
+        // Set by `init`, only read afterwards
+        #[frozen]
+        calibration: [u16; 4],
+    }
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(_: init::Context) -> (Shared, Local, init::Monotonics) {
+        rtic::pend(Interrupt::UART0);
+
+        (
+            Shared {
+                calibration: [100, 200, 300, 400],
+            },
+            Local {},
+            init::Monotonics(),
+        )
+    }
+
+    #[task(binds = UART0, shared = [&calibration])]
+    fn uart0(cx: uart0::Context) {
+        // A `&'static` reference, it can be kept beyond the task
+        let calibration: &'static [u16; 4] = cx.shared.calibration;
+        hprintln!("UART0: calibration[0] = {}", calibration[0]).unwrap();
+
+        rtic::pend(Interrupt::UART1);
+    }
+
+    #[task(binds = UART1, priority = 2, shared = [&calibration])]
+    fn uart1(cx: uart1::Context) {
+        hprintln!("UART1: calibration[3] = {}", cx.shared.calibration[3]).unwrap();
+
+        debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+    }
+}
@@ -0,0 +1,84 @@
+//! examples/in-place.rs
+
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_semihosting as _;
+
+/// A DMA descriptor
+#[derive(Clone, Copy)]
+pub struct Descriptor {
+    pub addr: u32,
+    pub len: u32,
+    pub flags: u32,
+    pub next: u32,
+}
+
+/// 16 KiB worth of descriptors
+pub const N: usize = 1024;
+
+#[rtic::app(device = lm3s6965)]
+mod app {
+    use super::{Descriptor, N};
+    use cortex_m_semihosting::{debug, hprintln};
+    use rtic::InPlace;
+
+    #[shared]
+    struct Shared {
+        // Written by `init` directly into its static storage
+        #[init(in_place)]
+        descriptors: [Descriptor; N],
+    }
+
+    #[local]
+    struct Local {
+        #[init(in_place)]
+        scratch: [u8; 1024],
+    }
+
+    #[init]
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+        // Each descriptor is written straight into the storage, the array is never on the stack
+        let descriptors = InPlace::from_fn(cx.uninit.descriptors, |i| Descriptor {
+            addr: 0x2000_0000 + 16 * i as u32,
+            len: 16,
+            flags: 0,
+            next: (i as u32 + 1) % N as u32,
+        });
+
+        // Or through the slot itself
+        let scratch = InPlace::init_with(cx.uninit.scratch, |slot| {
+            let first = slot.as_mut_ptr() as *mut u8;
+            // NOTE(unsafe) zero is a valid `u8`, and the whole array is written
+            unsafe {
+                first.write_bytes(0, 1024);
+                &mut *slot.as_mut_ptr()
+            }
+        });
+
+        rtic::pend(lm3s6965::Interrupt::UART0);
+
+        (
+            Shared { descriptors },
+            Local { scratch },
+            init::Monotonics(),
+        )
+    }
+
+    #[task(binds = UART0, shared = [descriptors], local = [scratch])]
+    fn uart0(mut cx: uart0::Context) {
+        let scratch = cx.local.scratch;
+
+        cx.shared.descriptors.lock(|descriptors| {
+            let last = &descriptors[N - 1];
+            scratch[0] = last.len as u8;
+
+            hprintln!("last: addr = {:#x}, next = {}", last.addr, last.next).unwrap();
+        });
+
+        hprintln!("scratch[0] = {}", scratch[0]).unwrap();
+
+        debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+    }
+}
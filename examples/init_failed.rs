@@ -0,0 +1,50 @@
+//! examples/init_failed.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_semihosting as _;
+
+#[derive(Debug)]
+pub enum InitError {
+    SensorMissing,
+}
+
+#[rtic::app(device = lm3s6965)]
+mod app {
+    use super::InitError;
+    use cortex_m_semihosting::{debug, hprintln};
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(_: init::Context) -> Result<(Shared, Local, init::Monotonics), InitError> {
+        hprintln!("init").unwrap();
+
+        probe_sensor()?;
+
+        Ok((Shared {}, Local {}, init::Monotonics()))
+    }
+
+    // Runs with interrupts still disabled when `init` returns an error
+    #[init_failed]
+    fn on_fail(err: InitError) -> ! {
+        hprintln!("init failed: {:?}", err).unwrap();
+
+        debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+
+        loop {
+            cortex_m::asm::nop();
+        }
+    }
+
+    fn probe_sensor() -> Result<(), InitError> {
+        Err(InitError::SensorMissing)
+    }
+}
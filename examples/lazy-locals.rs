@@ -0,0 +1,57 @@
+//! examples/lazy-locals.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_semihosting as _;
+
+#[rtic::app(device = lm3s6965, dispatchers = [UART0])]
+mod app {
+    use cortex_m_semihosting::{debug, hprintln};
+    use heapless::{String, Vec};
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(_: init::Context) -> (Shared, Local, init::Monotonics) {
+        foo::spawn().unwrap();
+        foo::spawn().unwrap();
+
+        (Shared {}, Local {}, init::Monotonics())
+    }
+
+    // `#[lazy]` locals are initialized right before `init` runs, so they can use non-const
+    // expressions
+    #[task(capacity = 2, local = [
+        #[lazy] name: String<8> = String::from("foo"),
+        #[lazy] squares: Vec<u32, 4> = squares(),
+        count: u32 = 0,
+    ])]
+    fn foo(cx: foo::Context) {
+        *cx.local.count += 1;
+
+        hprintln!(
+            "{} #{}: {:?}",
+            cx.local.name,
+            cx.local.count,
+            cx.local.squares
+        )
+        .unwrap();
+
+        cx.local.squares.pop();
+
+        if *cx.local.count == 2 {
+            debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+        }
+    }
+
+    fn squares() -> Vec<u32, 4> {
+        (1..=4).map(|i| i * i).collect()
+    }
+}
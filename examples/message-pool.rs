@@ -0,0 +1,51 @@
+//! examples/message-pool.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_semihosting as _;
+
+#[rtic::app(device = lm3s6965, dispatchers = [SSI0], pools = [messages = 2])]
+mod app {
+    use cortex_m_semihosting::{debug, hprintln};
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(_: init::Context) -> (Shared, Local, init::Monotonics) {
+        // `frame` and `command` share two message slots, each the size of the largest message
+        frame::spawn([1; 256]).unwrap();
+        command::spawn(42).unwrap();
+
+        // Every slot of the pool is taken
+        assert!(frame::spawn([2; 256]).is_err());
+        hprintln!("pool is full").unwrap();
+
+        (Shared {}, Local {}, init::Monotonics())
+    }
+
+    #[task(pool = messages)]
+    fn frame(_: frame::Context, frame: [u8; 256]) {
+        let sum: u32 = frame.iter().map(|&byte| u32::from(byte)).sum();
+
+        hprintln!("frame: sum = {}", sum).unwrap();
+    }
+
+    #[task(pool = messages)]
+    fn command(_: command::Context, command: u8) {
+        hprintln!("command: {}", command).unwrap();
+
+        // The slot of `frame` was handed back, `command` can use it
+        command::spawn(command + 1).ok();
+
+        if command == 43 {
+            debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+        }
+    }
+}
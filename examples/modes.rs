@@ -0,0 +1,67 @@
+//! examples/modes.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_semihosting as _;
+
+// The first mode is the one the application starts in
+#[rtic::app(device = lm3s6965, dispatchers = [SSI0], modes = [Normal, Safe])]
+mod app {
+    use cortex_m_semihosting::{debug, hprintln};
+    use lm3s6965::Interrupt;
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(_: init::Context) -> (Shared, Local, init::Monotonics) {
+        control::spawn().unwrap();
+
+        (Shared {}, Local {}, init::Monotonics())
+    }
+
+    // Tasks without `modes` run in every mode
+    #[task]
+    fn control(_: control::Context) {
+        sample::spawn(1).unwrap();
+
+        set_mode(Mode::Safe);
+
+        // Masked in `Safe` mode, this doesn't run
+        rtic::pend(Interrupt::UART0);
+
+        if sample::spawn(2).is_err() {
+            hprintln!("sample can't run in {:?} mode", mode()).unwrap();
+        }
+
+        done::spawn().unwrap();
+    }
+
+    #[task]
+    fn done(_: done::Context) {
+        debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+    }
+
+    #[task(binds = UART0, modes = [Normal])]
+    fn sensor(_: sensor::Context) {
+        hprintln!("sensor").unwrap();
+    }
+
+    #[task(modes = [Normal])]
+    fn sample(_: sample::Context, n: u32) {
+        // `sample(1)` was spawned in `Normal` mode, but it's dropped as it didn't start before
+        // the switch
+        hprintln!("sample {}", n).unwrap();
+    }
+
+    #[on_mode_change]
+    fn on_mode_change(from: Mode, to: Mode) {
+        hprintln!("{:?} -> {:?}", from, to).unwrap();
+    }
+}
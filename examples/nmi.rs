@@ -0,0 +1,60 @@
+//! examples/nmi.rs
+
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_semihosting as _;
+
+#[rtic::app(device = lm3s6965)]
+mod app {
+    use core::sync::atomic::{AtomicU32, Ordering};
+    use cortex_m_semihosting::{debug, hprintln};
+    use lm3s6965::Interrupt;
+
+    #[shared]
+    struct Shared {
+        // Shared by reference, the NMI handler can't be masked by a lock
+        events: AtomicU32,
+        // Only the NMI handler uses this one
+        #[lock_free]
+        nmi_count: u32,
+    }
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(_: init::Context) -> (Shared, Local, init::Monotonics) {
+        rtic::pend(Interrupt::UART0);
+
+        (
+            Shared {
+                events: AtomicU32::new(0),
+                nmi_count: 0,
+            },
+            Local {},
+            init::Monotonics(),
+        )
+    }
+
+    #[task(binds = UART0, shared = [&events])]
+    fn uart0(cx: uart0::Context) {
+        cx.shared.events.fetch_add(1, Ordering::Relaxed);
+
+        // Pend the NMI
+        unsafe { (*cortex_m::peripheral::SCB::ptr()).icsr.write(1 << 31) };
+
+        hprintln!("events = {}", cx.shared.events.load(Ordering::Relaxed)).unwrap();
+
+        debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+    }
+
+    #[task(binds = NonMaskableInt, shared = [&events, nmi_count])]
+    fn nmi(cx: nmi::Context) {
+        *cx.shared.nmi_count += 1;
+        cx.shared.events.fetch_add(1, Ordering::Relaxed);
+
+        hprintln!("NMI #{}", cx.shared.nmi_count).unwrap();
+    }
+}
@@ -0,0 +1,41 @@
+//! examples/order.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_semihosting as _;
+
+#[rtic::app(device = lm3s6965, dispatchers = [SSI0])]
+mod app {
+    use cortex_m_semihosting::{debug, hprintln};
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(_: init::Context) -> (Shared, Local, init::Monotonics) {
+        logger::spawn().unwrap();
+        sample::spawn().unwrap();
+
+        (Shared {}, Local {}, init::Monotonics())
+    }
+
+    // Tasks without an `order` run after the ones that have one
+    #[task]
+    fn logger(_: logger::Context) {
+        hprintln!("logger").unwrap();
+
+        debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+    }
+
+    // Spawned last, but it runs first
+    #[task(order = 0)]
+    fn sample(_: sample::Context) {
+        hprintln!("sample").unwrap();
+    }
+}
@@ -0,0 +1,55 @@
+//! examples/rate-limit.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_semihosting as _;
+
+#[rtic::app(device = lm3s6965)]
+mod app {
+    use cortex_m_semihosting::{debug, hprintln};
+    use lm3s6965::Interrupt;
+    use rtic::time::duration::*;
+    use systick_monotonic::Systick;
+
+    #[monotonic(binds = SysTick, default = true)]
+    type MyMono = Systick<100>; // 100 Hz / 10 ms granularity
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+        let mono = Systick::new(cx.core.SYST, 12_000_000);
+
+        rtic::pend(Interrupt::GPIOA);
+
+        (Shared {}, Local {}, init::Monotonics(mono))
+    }
+
+    // After each run the interrupt is masked for 100 ms, measured with the default monotonic
+    #[task(binds = GPIOA, min_interarrival = 100.milliseconds(), local = [runs: u32 = 0])]
+    fn gpioa(cx: gpioa::Context) {
+        *cx.local.runs += 1;
+
+        hprintln!(
+            "gpioa run {}, suppressed {}",
+            cx.local.runs,
+            gpioa::suppressed()
+        )
+        .unwrap();
+
+        if *cx.local.runs == 1 {
+            // A chattering input, these only run the task once, after the window
+            rtic::pend(Interrupt::GPIOA);
+            rtic::pend(Interrupt::GPIOA);
+        } else {
+            debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+        }
+    }
+}
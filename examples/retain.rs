@@ -0,0 +1,45 @@
+//! examples/retain.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_semihosting as _;
+
+#[rtic::app(device = lm3s6965)]
+mod app {
+    use cortex_m::peripheral::SCB;
+    use cortex_m_semihosting::{debug, hprintln};
+    use lm3s6965::Interrupt;
+
+    #[shared]
+    struct Shared {
+        // Keeps its value across a warm reset
+        #[retain]
+        boots: u32,
+    }
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+        // `None` after a power-on reset
+        let boots = cx.retained.boots.unwrap_or(0) + 1;
+        hprintln!("boot #{}", boots).unwrap();
+
+        rtic::pend(Interrupt::UART0);
+
+        (Shared { boots }, Local {}, init::Monotonics())
+    }
+
+    #[task(binds = UART0, shared = [boots])]
+    fn uart0(mut cx: uart0::Context) {
+        if cx.shared.boots.lock(|boots| *boots) < 3 {
+            SCB::sys_reset();
+        } else {
+            debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+        }
+    }
+}
@@ -0,0 +1,49 @@
+//! examples/signal.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_semihosting as _;
+
+#[rtic::app(device = lm3s6965, dispatchers = [SSI0])]
+mod app {
+    use cortex_m_semihosting::{debug, hprintln};
+    use lm3s6965::Interrupt;
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(_: init::Context) -> (Shared, Local, init::Monotonics) {
+        rtic::pend(Interrupt::UART0);
+        rtic::pend(Interrupt::UART1);
+
+        (Shared {}, Local {}, init::Monotonics())
+    }
+
+    #[task(binds = UART0, priority = 2)]
+    fn uart0(_: uart0::Context) {
+        // `data_ready` is still pending after the first call, the other ones do nothing
+        data_ready::signal().unwrap();
+        data_ready::signal().unwrap();
+    }
+
+    #[task(binds = UART1, priority = 2)]
+    fn uart1(_: uart1::Context) {
+        data_ready::signal().unwrap();
+    }
+
+    #[task(local = [times: u32 = 0])]
+    fn data_ready(cx: data_ready::Context) {
+        *cx.local.times += 1;
+
+        hprintln!("data_ready ran {} time(s)", cx.local.times).unwrap();
+
+        debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+    }
+}
@@ -0,0 +1,55 @@
+//! examples/spawn-with.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_semihosting as _;
+
+/// A 1 KiB frame
+pub struct Frame {
+    pub seq: u32,
+    pub data: [u8; 1024],
+}
+
+#[rtic::app(device = lm3s6965, dispatchers = [SSI0])]
+mod app {
+    use super::Frame;
+    use cortex_m_semihosting::{debug, hprintln};
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(_: init::Context) -> (Shared, Local, init::Monotonics) {
+        for seq in 1..=2 {
+            // The frame is written straight into the buffer of `consume`
+            consume::spawn_with(|slot| {
+                slot.write(Frame {
+                    seq,
+                    data: [seq as u8; 1024],
+                })
+            })
+            .ok()
+            .unwrap();
+        }
+
+        (Shared {}, Local {}, init::Monotonics())
+    }
+
+    // The frame stays in the buffer while the task runs
+    #[task(capacity = 2, by_ref)]
+    fn consume(_: consume::Context, frame: &Frame) {
+        let sum: u32 = frame.data.iter().map(|&byte| u32::from(byte)).sum();
+
+        hprintln!("frame {}: sum = {}", frame.seq, sum).unwrap();
+
+        if frame.seq == 2 {
+            debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+        }
+    }
+}
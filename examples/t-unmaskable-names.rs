@@ -0,0 +1,50 @@
+//! [compile-pass] Check that tasks RTIC can't mask may call unrelated APIs named like the spawn API
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_semihosting as _;
+
+/// A driver whose API happens to share names with the spawn API
+pub struct Dma;
+
+impl Dma {
+    pub fn cancel(&mut self) {}
+
+    pub fn set_mode(&mut self, _circular: bool) {}
+
+    pub fn call(_channel: u8) {}
+}
+
+#[rtic::app(device = lm3s6965, dispatchers = [UART1])]
+mod app {
+    use super::Dma;
+    use cortex_m_semihosting::debug;
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(_: init::Context) -> (Shared, Local, init::Monotonics) {
+        debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+
+        (Shared {}, Local {}, init::Monotonics())
+    }
+
+    #[task(binds = NonMaskableInt, local = [dma: Dma = Dma])]
+    fn nmi(cx: nmi::Context) {
+        let dma = cx.local.dma;
+
+        dma.cancel();
+        dma.set_mode(false);
+        Dma::call(0);
+    }
+
+    #[task]
+    fn foo(_: foo::Context) {}
+}
@@ -0,0 +1,57 @@
+//! examples/task-metadata.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_semihosting as _;
+
+#[rtic::app(device = lm3s6965, dispatchers = [SSI0])]
+mod app {
+    use cortex_m_semihosting::{debug, hprintln};
+
+    #[shared]
+    struct Shared {
+        counter: u32,
+    }
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(_: init::Context) -> (Shared, Local, init::Monotonics) {
+        foo::spawn().unwrap();
+
+        (Shared { counter: 0 }, Local {}, init::Monotonics())
+    }
+
+    #[task(binds = UART0, priority = 2, shared = [counter])]
+    fn uart0(_: uart0::Context) {}
+
+    #[task(capacity = 4, shared = [counter])]
+    fn foo(_: foo::Context) {
+        for task in Task::ALL {
+            let metadata = task.metadata();
+
+            hprintln!(
+                "{}: {:?}, priority {}, binds {:?}, capacity {:?}",
+                task.name(),
+                metadata.kind,
+                metadata.priority,
+                metadata.binds,
+                metadata.capacity,
+            )
+            .unwrap();
+
+            for resource in metadata.shared_resources {
+                hprintln!("  {} (ceiling {})", resource.name, resource.ceiling).unwrap();
+            }
+        }
+
+        // The constants of a task's module are available on their own as well
+        hprintln!("foo::PRIORITY = {}", foo::PRIORITY).unwrap();
+
+        debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+    }
+}
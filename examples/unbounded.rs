@@ -0,0 +1,47 @@
+//! examples/unbounded.rs
+
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+
+use alloc_cortex_m::CortexMHeap;
+use panic_semihosting as _;
+
+#[global_allocator]
+static ALLOCATOR: CortexMHeap = CortexMHeap::empty();
+
+#[rtic::app(device = lm3s6965, dispatchers = [SSI0])]
+mod app {
+    use alloc::string::String;
+    use cortex_m_semihosting::{debug, hprintln};
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[init(local = [heap: [u8; 1024] = [0; 1024]])]
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+        // NOTE(unsafe) the heap is initialized once, before anything is allocated
+        unsafe { super::ALLOCATOR.init(cx.local.heap.as_ptr() as usize, cx.local.heap.len()) }
+
+        // There's no static capacity to tune, spawning only fails when the heap is exhausted
+        for word in ["the", "quick", "brown", "fox"].iter() {
+            word::spawn(String::from(*word)).unwrap();
+        }
+
+        (Shared {}, Local {}, init::Monotonics())
+    }
+
+    #[task(capacity = unbounded)]
+    fn word(_: word::Context, word: String) {
+        hprintln!("{}", word).unwrap();
+
+        if word == "fox" {
+            debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! examples/watchdog.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_semihosting as _;
+
+#[rtic::app(device = lm3s6965, dispatchers = [SSI0])]
+mod app {
+    use cortex_m_semihosting::{debug, hprintln};
+    use rtic::time::duration::*;
+    use systick_monotonic::Systick;
+
+    #[monotonic(binds = SysTick, default = true)]
+    type MyMono = Systick<100>; // 100 Hz / 10 ms granularity
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+        let mono = Systick::new(cx.core.SYST, 12_000_000);
+
+        heartbeat::spawn().unwrap();
+
+        (Shared {}, Local {}, init::Monotonics(mono))
+    }
+
+    // Must complete at least once every 80 ms
+    #[task(watchdog = 80.milliseconds(), local = [count: u32 = 0])]
+    fn heartbeat(cx: heartbeat::Context) {
+        *cx.local.count += 1;
+        hprintln!("heartbeat {}", cx.local.count).unwrap();
+
+        // Stops beating after the third time
+        if *cx.local.count < 3 {
+            heartbeat::spawn_after(50.milliseconds()).unwrap();
+        }
+    }
+
+    // Called each time every supervised task completed in its last window
+    #[watchdog_kick]
+    fn watchdog_kick() {
+        hprintln!("kick").unwrap();
+    }
+
+    // Called when a supervised task didn't complete in its window
+    #[on_watchdog_timeout]
+    fn on_watchdog_timeout(task: Task) {
+        hprintln!("watchdog timeout: {:?}", task).unwrap();
+
+        debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+    }
+}
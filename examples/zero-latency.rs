@@ -0,0 +1,78 @@
+//! examples/zero-latency.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_semihosting as _;
+
+#[rtic::app(device = lm3s6965)]
+mod app {
+    use core::sync::atomic::{AtomicU32, Ordering};
+    use cortex_m_semihosting::{debug, hprintln};
+    use lm3s6965::Interrupt;
+
+    #[shared]
+    struct Shared {
+        shared: u32,
+        // zero latency tasks can only communicate through lock-free means
+        commutations: AtomicU32,
+    }
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(_: init::Context) -> (Shared, Local, init::Monotonics) {
+        rtic::pend(Interrupt::UART0);
+
+        (
+            Shared {
+                shared: 0,
+                commutations: AtomicU32::new(0),
+            },
+            Local {},
+            init::Monotonics(),
+        )
+    }
+
+    #[task(binds = UART0, shared = [shared, &commutations])]
+    fn foo(mut c: foo::Context) {
+        hprintln!("A").unwrap();
+
+        // `bar` runs at the highest priority available to normal tasks, so this lock masks every
+        // one of them
+        c.shared.shared.lock(|shared| {
+            *shared += 1;
+
+            rtic::pend(Interrupt::GPIOA);
+            rtic::pend(Interrupt::UART1);
+
+            hprintln!("C").unwrap();
+        });
+
+        hprintln!(
+            "E - commutations = {}",
+            c.shared.commutations.load(Ordering::Relaxed)
+        )
+        .unwrap();
+
+        debug::exit(debug::EXIT_SUCCESS); // Exit QEMU simulator
+    }
+
+    #[task(binds = UART1, priority = 7, shared = [shared])]
+    fn bar(mut c: bar::Context) {
+        c.shared.shared.lock(|shared| *shared += 1);
+
+        hprintln!("D").unwrap();
+    }
+
+    // never blocked by the lock above
+    #[task(binds = GPIOA, zero_latency, shared = [&commutations])]
+    fn motor(c: motor::Context) {
+        c.shared.commutations.fetch_add(1, Ordering::Relaxed);
+
+        hprintln!("B").unwrap();
+    }
+}
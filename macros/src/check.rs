@@ -1,15 +1,68 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 
-use proc_macro2::Span;
-use rtic_syntax::{analyze::Analysis, ast::App};
-use syn::{parse, Path};
+use proc_macro2::{Delimiter, Span, TokenStream as TokenStream2, TokenTree};
+use quote::quote;
+use rtic_syntax::{
+    analyze::Analysis,
+    ast::{Access, App, TaskLocal},
+};
+use syn::{parse, Expr, Ident, ItemFn, ItemType, Path, ReturnType, Signature, Stmt, Type};
+
+use crate::{codegen::util, preprocess::Extensions};
 
 pub struct Extra {
     pub device: Path,
     pub peripherals: bool,
+    pub zero_latency: BTreeSet<Ident>,
+    pub pre_init: Option<ItemFn>,
+    pub init_error: Option<Type>,
+    pub init_failed: Option<ItemFn>,
+    pub replies: BTreeMap<Ident, Type>,
+    /// The reference type `#[task(by_ref)]` tasks get their message as
+    pub by_ref: BTreeMap<Ident, Type>,
+    pub events: Vec<ItemType>,
+    /// The subscribers of each event
+    pub subscribers: BTreeMap<Ident, Vec<Ident>>,
+    /// Priority levels whose dispatcher runs the ready tasks in deadline order
+    pub edf_levels: BTreeSet<u8>,
+    pub deadlines: BTreeMap<Ident, Expr>,
+    pub orders: BTreeMap<Ident, u8>,
+    pub modes: Vec<Ident>,
+    pub task_modes: BTreeMap<Ident, Vec<Ident>>,
+    /// The message pools, their capacity, and the pool of each software task that uses one
+    pub pools: BTreeMap<Ident, u16>,
+    pub task_pools: BTreeMap<Ident, Ident>,
+    /// The capacities of software tasks that exceed 255
+    pub capacities: BTreeMap<Ident, u16>,
+    /// Software tasks whose messages are heap allocated
+    pub unbounded: BTreeSet<Ident>,
+    pub on_mode_change: Option<ItemFn>,
+    /// Rate limited hardware tasks: their minimum inter-arrival time and the monotonic measuring it
+    pub rate_limits: BTreeMap<Ident, (Expr, Ident)>,
+    /// Tasks with an execution budget: the budget and the monotonic measuring it
+    pub budgets: BTreeMap<Ident, (Expr, Ident)>,
+    pub budget_overrun: Option<ItemFn>,
+    /// Supervised tasks: the window they must complete in and the monotonic measuring it
+    pub watchdogs: BTreeMap<Ident, (Expr, Ident)>,
+    pub on_watchdog_timeout: Option<ItemFn>,
+    pub watchdog_kick: Option<ItemFn>,
+    pub fault: Option<ItemFn>,
+    /// Resources initialized at compile time instead of by `#[init]`
+    pub shared_inits: BTreeMap<Ident, Expr>,
+    pub local_inits: BTreeMap<Ident, Expr>,
+    /// Resources `#[init]` writes through `cx.uninit`
+    pub shared_in_place: BTreeSet<Ident>,
+    pub local_in_place: BTreeSet<Ident>,
+    /// Resources that survive a warm reset
+    pub shared_retained: BTreeSet<Ident>,
+    pub local_retained: BTreeSet<Ident>,
+    /// Shared resources only read after `#[init]`
+    pub frozen: BTreeSet<Ident>,
+    /// Declared locals initialized before `#[init]` runs: the task and the name of the local
+    pub lazy_locals: BTreeSet<(Ident, Ident)>,
 }
 
-pub fn app(app: &App, _analysis: &Analysis) -> parse::Result<Extra> {
+pub fn app(app: &App, _analysis: &Analysis, ext: Extensions) -> parse::Result<Extra> {
     // Check that external (device-specific) interrupts are not named after known (Cortex-M)
     // exceptions
     for name in app.args.extern_interrupts.keys() {
@@ -56,26 +109,639 @@ pub fn app(app: &App, _analysis: &Analysis) -> parse::Result<Extra> {
         return Err(parse::Error::new(first.unwrap().span(), &s));
     }
 
-    // Check that all exceptions are valid; only exceptions with configurable priorities are
-    // accepted
+    // The names the queue APIs hang off: `foo::spawn`, `Event::publish`, `MyMono::spawn_after`
+    let roots = app
+        .software_tasks
+        .keys()
+        .chain(ext.events.iter().map(|event| &event.ident))
+        .chain(app.monotonics.keys())
+        .map(|name| name.to_string())
+        .collect::<HashSet<_>>();
+
+    // Check the tasks bound to exceptions with a fixed priority. RTIC can't mask these so they may
+    // only touch resources that never need a lock and they can't spawn
     for (name, task) in &app.hardware_tasks {
-        let name_s = task.args.binds.to_string();
-        match &*name_s {
-            "NonMaskableInt" | "HardFault" => {
+        let binds = &task.args.binds;
+        if util::is_fixed_priority_exception(binds) {
+            check_unmaskable(app, &roots, name, &format!("`{}`", binds), |other| {
+                other == name
+            })?;
+        }
+    }
+
+    // Check the zero latency tasks. These run above every priority RTIC ever writes to BASEPRI
+    for name in &ext.zero_latency {
+        let task = if let Some(task) = app.hardware_tasks.get(name) {
+            task
+        } else {
+            return Err(parse::Error::new(
+                name.span(),
+                "only hardware tasks can be zero latency tasks",
+            ));
+        };
+
+        if util::is_fixed_priority_exception(&task.args.binds) {
+            return Err(parse::Error::new(
+                task.args.binds.span(),
+                "`NonMaskableInt` and `HardFault` can't be zero latency tasks",
+            ));
+        }
+
+        // All the zero latency tasks run at the same priority so they can share `#[lock_free]`
+        // resources among themselves
+        check_unmaskable(
+            app,
+            &roots,
+            name,
+            &format!("zero latency task `{}`", name),
+            |other| ext.zero_latency.contains(other),
+        )?;
+    }
+
+    if let Some(pre_init) = &ext.pre_init {
+        let sig = &pre_init.sig;
+        let valid = is_plain_fn(sig, 1) && matches!(sig.output, ReturnType::Default);
+
+        if !valid {
+            return Err(parse::Error::new(
+                sig.ident.span(),
+                format!(
+                    "the `#[pre_init]` function must have signature `fn({}::Context)`",
+                    sig.ident
+                ),
+            ));
+        }
+    }
+
+    match (&ext.init_error, &ext.init_failed) {
+        (Some(_), None) => {
+            return Err(parse::Error::new(
+                app.init.name.span(),
+                "a fallible `#[init]` requires an `#[init_failed]` function to handle its error",
+            ));
+        }
+
+        (None, Some(init_failed)) => {
+            return Err(parse::Error::new(
+                init_failed.sig.ident.span(),
+                "`#[init_failed]` can only be used when `#[init]` returns a `Result`",
+            ));
+        }
+
+        (Some(_), Some(init_failed)) => {
+            let sig = &init_failed.sig;
+            let valid = is_plain_fn(sig, 1)
+                && matches!(&sig.output, ReturnType::Type(_, ty) if matches!(**ty, Type::Never(_)));
+
+            if !valid {
                 return Err(parse::Error::new(
-                    name.span(),
-                    "only exceptions with configurable priority can be used as hardware tasks",
+                    sig.ident.span(),
+                    "the `#[init_failed]` function must have signature `fn(E) -> !`, where `E` is \
+                        the error type of `#[init]`",
                 ));
             }
+        }
 
+        (None, None) => {}
+    }
+
+    let mut subscribers = BTreeMap::new();
+    for event in &ext.events {
+        if subscribers.insert(event.ident.clone(), vec![]).is_some() {
+            return Err(parse::Error::new(
+                event.ident.span(),
+                "this event is declared more than once",
+            ));
+        }
+    }
+
+    for (task, events) in &ext.subscriptions {
+        if app.software_tasks[task].inputs.len() != 1 {
+            return Err(parse::Error::new(
+                task.span(),
+                "tasks that subscribe to events must take exactly one input, the message",
+            ));
+        }
+
+        for event in events {
+            if let Some(tasks) = subscribers.get_mut(event) {
+                tasks.push(task.clone());
+            } else {
+                return Err(parse::Error::new(
+                    event.span(),
+                    format!("event `{}` is not declared with `#[event]`", event),
+                ));
+            }
+        }
+    }
+
+    // Dispatchers are assigned to the priority levels from the highest one down, see `analyze`
+    let mut edf_levels = BTreeSet::new();
+    let levels = app
+        .software_tasks
+        .values()
+        .map(|task| task.args.priority)
+        .collect::<BTreeSet<_>>();
+    for (level, name) in levels.iter().rev().zip(app.args.extern_interrupts.keys()) {
+        if ext.edf_dispatchers.contains(name) {
+            edf_levels.insert(*level);
+        }
+    }
+
+    for name in &ext.edf_dispatchers {
+        let used = levels
+            .iter()
+            .rev()
+            .zip(app.args.extern_interrupts.keys())
+            .any(|(_, dispatcher)| dispatcher == name);
+        if !used {
+            return Err(parse::Error::new(
+                name.span(),
+                "this dispatcher doesn't run any software task so it can't be `edf`",
+            ));
+        }
+    }
+
+    if !edf_levels.is_empty() && !app.monotonics.values().any(|m| m.args.default) {
+        return Err(parse::Error::new(
+            ext.edf_dispatchers
+                .iter()
+                .next()
+                .expect("UNREACHABLE")
+                .span(),
+            "`edf` dispatchers measure deadlines with the default monotonic, \
+                `#[monotonic(.., default = true)]`",
+        ));
+    }
+
+    for (name, task) in &app.software_tasks {
+        let is_edf = edf_levels.contains(&task.args.priority);
+        match (is_edf, ext.deadlines.contains_key(name)) {
+            (true, false) => {
+                return Err(parse::Error::new(
+                    name.span(),
+                    "tasks dispatched by an `edf` dispatcher must have a `deadline`",
+                ));
+            }
+            (false, true) => {
+                return Err(parse::Error::new(
+                    name.span(),
+                    "only tasks dispatched by an `edf` dispatcher can have a `deadline`",
+                ));
+            }
             _ => {}
         }
     }
 
+    for name in ext.deadlines.keys() {
+        if !app.software_tasks.contains_key(name) {
+            return Err(parse::Error::new(
+                name.span(),
+                "only software tasks can have a `deadline`",
+            ));
+        }
+    }
+
+    for name in ext.orders.keys() {
+        let task = if let Some(task) = app.software_tasks.get(name) {
+            task
+        } else {
+            return Err(parse::Error::new(
+                name.span(),
+                "only software tasks can have an `order`",
+            ));
+        };
+
+        if edf_levels.contains(&task.args.priority) {
+            return Err(parse::Error::new(
+                name.span(),
+                "tasks dispatched by an `edf` dispatcher run in deadline order, \
+                    they can't have an `order`",
+            ));
+        }
+    }
+
+    let mut modes = BTreeSet::new();
+    for mode in &ext.modes {
+        if !modes.insert(mode) {
+            return Err(parse::Error::new(
+                mode.span(),
+                "this mode is declared more than once",
+            ));
+        }
+    }
+
+    for (name, task_modes) in &ext.task_modes {
+        if ext.modes.is_empty() {
+            return Err(parse::Error::new(
+                name.span(),
+                "`modes` can only be used when the `app` declares them, e.g. \
+                    `#[rtic::app(.., modes = [Normal, Safe])]`",
+            ));
+        }
+
+        if let Some(mode) = task_modes.iter().find(|mode| !modes.contains(mode)) {
+            return Err(parse::Error::new(
+                mode.span(),
+                format!("mode `{}` is not declared in the `app`", mode),
+            ));
+        }
+
+        // Switching modes masks and unmasks the hardware tasks
+        if let Some(task) = app.hardware_tasks.get(name) {
+            let binds = &task.args.binds;
+            if !util::is_maskable(binds) || ext.zero_latency.contains(name) {
+                return Err(parse::Error::new(
+                    binds.span(),
+                    format!(
+                        "`{}` can't be masked so its task can't be limited to some `modes`",
+                        binds
+                    ),
+                ));
+            }
+        }
+    }
+
+    if let Some(on_mode_change) = &ext.on_mode_change {
+        let sig = &on_mode_change.sig;
+        let valid = !ext.modes.is_empty()
+            && is_plain_fn(sig, 2)
+            && matches!(sig.output, ReturnType::Default);
+
+        if !valid {
+            return Err(parse::Error::new(
+                sig.ident.span(),
+                "the `#[on_mode_change]` function must have signature `fn(Mode, Mode)` and the \
+                    `app` must declare `modes`",
+            ));
+        }
+    }
+
+    let mut pools = BTreeMap::new();
+    for (pool, capacity) in &ext.pools {
+        if *capacity == 0 {
+            return Err(parse::Error::new(
+                pool.span(),
+                "a pool must have a capacity of at least 1",
+            ));
+        }
+
+        if pools.insert(pool.clone(), *capacity).is_some() {
+            return Err(parse::Error::new(
+                pool.span(),
+                "this pool is declared more than once",
+            ));
+        }
+    }
+
+    for (name, pool) in &ext.task_pools {
+        if !pools.contains_key(pool) {
+            return Err(parse::Error::new(
+                pool.span(),
+                format!("pool `{}` is not declared in the `app`", pool),
+            ));
+        }
+
+        // The dispatcher of the priority level is the only one that hands slots back
+        let priority = app.software_tasks[name].args.priority;
+        if let Some((other, _)) = ext.task_pools.iter().find(|(other, other_pool)| {
+            *other_pool == pool && app.software_tasks[*other].args.priority != priority
+        }) {
+            return Err(parse::Error::new(
+                name.span(),
+                format!(
+                    "tasks that share pool `{}` must have the same priority, `{}` doesn't",
+                    pool, other
+                ),
+            ));
+        }
+    }
+
+    if let Some(pool) = pools
+        .keys()
+        .find(|pool| !ext.task_pools.values().any(|used| used == *pool))
+    {
+        return Err(parse::Error::new(
+            pool.span(),
+            "this pool is not used by any task",
+        ));
+    }
+
+    // The messages of an unbounded task have no slot, which `call` and `by_ref` need. They also
+    // share one entry in the ready queue, so an `edf` dispatcher couldn't give each message the
+    // deadline it was spawned with
+    for name in &ext.unbounded {
+        let task = &app.software_tasks[name];
+        let conflict = if task.inputs.is_empty() {
+            Some(
+                "`capacity = unbounded` is for tasks that take a message, the others have `signal`",
+            )
+        } else if ext.replies.contains_key(name) {
+            Some("tasks with a `capacity = unbounded` can't return a reply")
+        } else if ext.by_ref.contains_key(name) {
+            Some("tasks with a `capacity = unbounded` can't get their message `by_ref`")
+        } else if edf_levels.contains(&task.args.priority) {
+            Some("tasks with a `capacity = unbounded` can't be dispatched by an `edf` dispatcher")
+        } else {
+            None
+        };
+
+        if let Some(message) = conflict {
+            return Err(parse::Error::new(name.span(), message));
+        }
+    }
+
+    for name in ext.task_monotonics.keys() {
+        if !ext.min_interarrivals.contains_key(name)
+            && !ext.budgets.contains_key(name)
+            && !ext.watchdogs.contains_key(name)
+        {
+            return Err(parse::Error::new(
+                name.span(),
+                "`monotonic` only selects the monotonic that measures `min_interarrival`, \
+                    `budget` and `watchdog`",
+            ));
+        }
+    }
+
+    let mut rate_limits = BTreeMap::new();
+    for (name, min_interarrival) in ext.min_interarrivals {
+        let task = if let Some(task) = app.hardware_tasks.get(&name) {
+            task
+        } else {
+            return Err(parse::Error::new(
+                name.span(),
+                "only hardware tasks can be rate limited",
+            ));
+        };
+
+        // The task is masked in the NVIC between runs, a zero latency task would race on the
+        // timer queue
+        let binds = &task.args.binds;
+        if util::is_exception(binds)
+            || util::is_fixed_priority_exception(binds)
+            || ext.zero_latency.contains(&name)
+        {
+            return Err(parse::Error::new(
+                binds.span(),
+                "only tasks bound to device interrupts, that aren't zero latency tasks, can have \
+                    a `min_interarrival`",
+            ));
+        }
+
+        let monotonic = task_monotonic(app, &ext.task_monotonics, &name, "min_interarrival")?;
+
+        rate_limits.insert(name, (min_interarrival, monotonic));
+    }
+
+    let mut budgets = BTreeMap::new();
+    for (name, budget) in ext.budgets {
+        // The overrun handler runs from the timer queue handler, which must preempt the task
+        let priority = maskable_task_priority(app, &ext.zero_latency, &name, "budget")?;
+        let monotonic = task_monotonic(app, &ext.task_monotonics, &name, "budget")?;
+        if let Some(max) = app.monotonics[&monotonic].args.priority {
+            if priority >= max {
+                return Err(parse::Error::new(
+                    name.span(),
+                    format!(
+                        "the priority of a task with a `budget` must be lower than the priority \
+                            of monotonic `{}`, which detects the overrun",
+                        monotonic
+                    ),
+                ));
+            }
+        }
+
+        budgets.insert(name, (budget, monotonic));
+    }
+
+    if !budgets.is_empty() && ext.budget_overrun.is_none() {
+        return Err(parse::Error::new(
+            budgets.keys().next().expect("UNREACHABLE").span(),
+            "tasks with a `budget` require a `#[budget_overrun]` function",
+        ));
+    }
+
+    if let Some(budget_overrun) = &ext.budget_overrun {
+        let sig = &budget_overrun.sig;
+        let valid = is_plain_fn(sig, 1) && matches!(sig.output, ReturnType::Default);
+
+        if !valid {
+            return Err(parse::Error::new(
+                sig.ident.span(),
+                "the `#[budget_overrun]` function must have signature `fn(Task)`",
+            ));
+        }
+    }
+
+    let mut watchdogs = BTreeMap::new();
+    for (name, window) in ext.watchdogs {
+        maskable_task_priority(app, &ext.zero_latency, &name, "watchdog")?;
+        let monotonic = task_monotonic(app, &ext.task_monotonics, &name, "watchdog")?;
+
+        watchdogs.insert(name, (window, monotonic));
+    }
+
+    match (watchdogs.keys().next(), &ext.on_watchdog_timeout) {
+        (Some(name), None) => {
+            return Err(parse::Error::new(
+                name.span(),
+                "tasks with a `watchdog` require an `#[on_watchdog_timeout]` function",
+            ));
+        }
+
+        (None, Some(on_watchdog_timeout)) => {
+            return Err(parse::Error::new(
+                on_watchdog_timeout.sig.ident.span(),
+                "`#[on_watchdog_timeout]` can only be used when some task has a `watchdog`",
+            ));
+        }
+
+        (_, on_watchdog_timeout) => {
+            if let Some(on_watchdog_timeout) = on_watchdog_timeout {
+                let sig = &on_watchdog_timeout.sig;
+                if !is_plain_fn(sig, 1) || !matches!(sig.output, ReturnType::Default) {
+                    return Err(parse::Error::new(
+                        sig.ident.span(),
+                        "the `#[on_watchdog_timeout]` function must have signature `fn(Task)`",
+                    ));
+                }
+            }
+        }
+    }
+
+    if let Some(watchdog_kick) = &ext.watchdog_kick {
+        let sig = &watchdog_kick.sig;
+        if watchdogs.is_empty() {
+            return Err(parse::Error::new(
+                sig.ident.span(),
+                "`#[watchdog_kick]` can only be used when some task has a `watchdog`",
+            ));
+        }
+
+        if !is_plain_fn(sig, 0) || !matches!(sig.output, ReturnType::Default) {
+            return Err(parse::Error::new(
+                sig.ident.span(),
+                "the `#[watchdog_kick]` function must have signature `fn()`",
+            ));
+        }
+    }
+
+    if let Some(fault) = &ext.fault {
+        let sig = &fault.sig;
+        if !is_plain_fn(sig, 1) || !matches!(sig.output, ReturnType::Default) {
+            return Err(parse::Error::new(
+                sig.ident.span(),
+                "the `#[fault]` function must have signature `fn(FaultInfo)`",
+            ));
+        }
+
+        // The `#[fault]` function is called from the `HardFault` handler
+        if let Some(task) = app
+            .hardware_tasks
+            .values()
+            .find(|task| task.args.binds == "HardFault")
+        {
+            return Err(parse::Error::new(
+                task.args.binds.span(),
+                "tasks can't be bound to `HardFault` when there's a `#[fault]` function",
+            ));
+        }
+    }
+
+    // A retained resource keeps its value or gets a new one from `#[init]`
+    if let Some(name) = ext
+        .shared_retained
+        .iter()
+        .filter(|name| ext.shared_inits.contains_key(*name))
+        .chain(
+            ext.local_retained
+                .iter()
+                .filter(|name| ext.local_inits.contains_key(*name)),
+        )
+        .next()
+    {
+        return Err(parse::Error::new(
+            name.span(),
+            "`#[retain]` resources are initialized by `#[init]`, they can't have `#[init(..)]`",
+        ));
+    }
+
+    // The check over a retained value covers all of its bytes once they're written
+    if let Some(name) = ext
+        .shared_retained
+        .iter()
+        .filter(|name| ext.shared_in_place.contains(*name))
+        .chain(
+            ext.local_retained
+                .iter()
+                .filter(|name| ext.local_in_place.contains(*name)),
+        )
+        .next()
+    {
+        return Err(parse::Error::new(
+            name.span(),
+            "`#[retain]` resources can't be written in place, `#[init(in_place)]`",
+        ));
+    }
+
+    // The check over a retained value is updated when the lock is released or when the task
+    // returns, `#[idle]` never does
+    if let Some(idle) = &app.idle {
+        let local = idle
+            .args
+            .local_resources
+            .iter()
+            .filter(|(name, local)| {
+                matches!(local, TaskLocal::External) && ext.local_retained.contains(*name)
+            })
+            .map(|(name, _)| name);
+        let shared = idle
+            .args
+            .shared_resources
+            .iter()
+            .filter(|(name, access)| {
+                ext.shared_retained.contains(*name)
+                    && access.is_exclusive()
+                    && app.shared_resources[*name].properties.lock_free
+            })
+            .map(|(name, _)| name);
+
+        if let Some(name) = local.chain(shared).next() {
+            return Err(parse::Error::new(
+                name.span(),
+                "`#[idle]` can only access `#[retain]` resources through a lock or by shared \
+                 reference",
+            ));
+        }
+    }
+
+    // Nothing may mutate a frozen resource once `#[init]` returns
+    let accesses = app
+        .idle
+        .iter()
+        .map(|idle| &idle.args.shared_resources)
+        .chain(
+            app.hardware_tasks
+                .values()
+                .map(|task| &task.args.shared_resources),
+        )
+        .chain(
+            app.software_tasks
+                .values()
+                .map(|task| &task.args.shared_resources),
+        );
+    for resources in accesses {
+        for (name, access) in resources {
+            if ext.frozen.contains(name) && access.is_exclusive() {
+                return Err(parse::Error::new(
+                    name.span(),
+                    format!(
+                        "`#[frozen]` resources can only be accessed by shared reference, `&{}`",
+                        name
+                    ),
+                ));
+            }
+        }
+    }
+
     if let Some(device) = app.args.device.clone() {
         Ok(Extra {
             device,
             peripherals: app.args.peripherals,
+            zero_latency: ext.zero_latency,
+            pre_init: ext.pre_init,
+            init_error: ext.init_error,
+            init_failed: ext.init_failed,
+            replies: ext.replies,
+            by_ref: ext.by_ref,
+            events: ext.events,
+            subscribers,
+            edf_levels,
+            deadlines: ext.deadlines,
+            orders: ext.orders,
+            modes: ext.modes,
+            task_modes: ext.task_modes,
+            pools,
+            task_pools: ext.task_pools,
+            capacities: ext.capacities,
+            unbounded: ext.unbounded,
+            on_mode_change: ext.on_mode_change,
+            rate_limits,
+            budgets,
+            budget_overrun: ext.budget_overrun,
+            watchdogs,
+            on_watchdog_timeout: ext.on_watchdog_timeout,
+            watchdog_kick: ext.watchdog_kick,
+            fault: ext.fault,
+            shared_inits: ext.shared_inits,
+            local_inits: ext.local_inits,
+            shared_in_place: ext.shared_in_place,
+            local_in_place: ext.local_in_place,
+            shared_retained: ext.shared_retained,
+            local_retained: ext.local_retained,
+            frozen: ext.frozen,
+            lazy_locals: ext.lazy_locals,
         })
     } else {
         Err(parse::Error::new(
@@ -84,3 +750,258 @@ pub fn app(app: &App, _analysis: &Analysis) -> parse::Result<Extra> {
         ))
     }
 }
+
+// The priority of task `name`, which must be a task RTIC can mask so it can have a `what`
+fn maskable_task_priority(
+    app: &App,
+    zero_latency: &BTreeSet<Ident>,
+    name: &Ident,
+    what: &str,
+) -> parse::Result<u8> {
+    if let Some(task) = app.software_tasks.get(name) {
+        Ok(task.args.priority)
+    } else if let Some(task) = app.hardware_tasks.get(name) {
+        let binds = &task.args.binds;
+        if util::is_fixed_priority_exception(binds) || zero_latency.contains(name) {
+            return Err(parse::Error::new(
+                binds.span(),
+                format!("tasks that can't be masked can't have a `{}`", what),
+            ));
+        }
+
+        Ok(task.args.priority)
+    } else {
+        Err(parse::Error::new(
+            name.span(),
+            format!("only tasks can have a `{}`", what),
+        ))
+    }
+}
+
+// Whether `sig` is a plain function, not `const`, `async`, `unsafe`, `extern` or generic, taking
+// `inputs` arguments
+fn is_plain_fn(sig: &Signature, inputs: usize) -> bool {
+    sig.constness.is_none()
+        && sig.asyncness.is_none()
+        && sig.unsafety.is_none()
+        && sig.abi.is_none()
+        && sig.generics.params.is_empty()
+        && sig.inputs.len() == inputs
+}
+
+// The monotonic that measures the `what` of task `name`: the one given with `monotonic = ..` or the
+// default one
+fn task_monotonic(
+    app: &App,
+    task_monotonics: &BTreeMap<Ident, Ident>,
+    name: &Ident,
+    what: &str,
+) -> parse::Result<Ident> {
+    match task_monotonics.get(name) {
+        Some(monotonic) => {
+            if app.monotonics.values().any(|m| m.ident == *monotonic) {
+                Ok(monotonic.clone())
+            } else {
+                Err(parse::Error::new(
+                    monotonic.span(),
+                    format!("monotonic `{}` is not declared", monotonic),
+                ))
+            }
+        }
+
+        None => {
+            if let Some(m) = util::default_monotonic(app) {
+                Ok(m.ident.clone())
+            } else {
+                Err(parse::Error::new(
+                    name.span(),
+                    format!("a `monotonic` is needed to measure `{}`", what),
+                ))
+            }
+        }
+    }
+}
+
+// Checks a task that RTIC can't mask: it may only access resources by reference, or `#[lock_free]`
+// resources used by no task other than its `peers`, and it's not allowed to spawn, cancel or
+// reschedule tasks or to change the mode
+fn check_unmaskable(
+    app: &App,
+    roots: &HashSet<String>,
+    name: &Ident,
+    what: &str,
+    peers: impl Fn(&Ident) -> bool,
+) -> parse::Result<()> {
+    let task = &app.hardware_tasks[name];
+
+    for (res, access) in &task.args.shared_resources {
+        let lock_free = app
+            .shared_resources
+            .get(res)
+            .map(|r| r.properties.lock_free)
+            .unwrap_or(false);
+
+        let mut others = shared_resource_accesses(app, res).filter(|(task, _)| *task != name);
+
+        if access.is_exclusive() {
+            if !lock_free || others.any(|(other, _)| !peers(other)) {
+                return Err(parse::Error::new(
+                    res.span(),
+                    format!(
+                        "{} can't be masked: only `#[lock_free]` resources used by no other \
+                            task can be accessed by mutable reference",
+                        what
+                    ),
+                ));
+            }
+        } else if others.any(|(_, access)| access.is_exclusive()) {
+            return Err(parse::Error::new(
+                res.span(),
+                format!(
+                    "{} can't be masked: resources it shares must be accessed by \
+                        reference (`&{}`) by all tasks",
+                    what, res
+                ),
+            ));
+        }
+    }
+
+    if let Some((span, action)) = queue_call(&task.stmts, roots, &app.name.to_string()) {
+        return Err(parse::Error::new(
+            span,
+            format!("{} can't be masked: it's not allowed to {}", what, action),
+        ));
+    }
+
+    Ok(())
+}
+
+// All the tasks that access the shared resource `res`, and how they access it
+fn shared_resource_accesses<'a>(
+    app: &'a App,
+    res: &'a Ident,
+) -> impl Iterator<Item = (&'a Ident, &'a Access)> {
+    let idle = app
+        .idle
+        .iter()
+        .filter_map(move |idle| Some((&idle.name, idle.args.shared_resources.get(res)?)));
+    let hardware_tasks = app
+        .hardware_tasks
+        .iter()
+        .filter_map(move |(name, task)| Some((name, task.args.shared_resources.get(res)?)));
+    let software_tasks = app
+        .software_tasks
+        .iter()
+        .filter_map(move |(name, task)| Some((name, task.args.shared_resources.get(res)?)));
+
+    idle.chain(hardware_tasks).chain(software_tasks)
+}
+
+// Finds a use of the APIs that go through the queues of RTIC in `stmts`: the `spawn` API (e.g.
+// `foo::spawn`, `foo::Mono::spawn_after`, `Event::publish`) and the `cancel` and `reschedule`
+// methods of spawn handles, on paths rooted at one of `roots` (the software tasks, the events and
+// the monotonics), and calls to `set_mode`
+//
+// This is lexical and best-effort, calls made through a `use` of the function itself, through a
+// spawn handle stored in a resource or through a helper function aren't found. The APIs fail at
+// runtime when called from these tasks anyway
+fn queue_call(
+    stmts: &[Stmt],
+    roots: &HashSet<String>,
+    app_name: &str,
+) -> Option<(Span, &'static str)> {
+    fn find(
+        ts: TokenStream2,
+        roots: &HashSet<String>,
+        app_name: &str,
+    ) -> Option<(Span, &'static str)> {
+        let tts = ts.into_iter().collect::<Vec<_>>();
+        // The first segment of the path, or of the method chain, the current token is part of
+        let mut root: Option<String> = None;
+        let mut colons = 0;
+        let mut dot = false;
+
+        for (i, tt) in tts.iter().enumerate() {
+            match tt {
+                TokenTree::Group(group) => {
+                    if let Some(found) = find(group.stream(), roots, app_name) {
+                        return Some(found);
+                    }
+
+                    // The arguments of a call or an index continue the method chain
+                    if group.delimiter() == Delimiter::Brace {
+                        root = None;
+                    }
+                    colons = 0;
+                    dot = false;
+                }
+
+                TokenTree::Punct(punct) if punct.as_char() == ':' => colons += 1,
+
+                TokenTree::Punct(punct) if punct.as_char() == '.' => {
+                    colons = 0;
+                    dot = true;
+                }
+
+                TokenTree::Ident(ident) => {
+                    let ident_s = ident.to_string();
+                    let is_call = matches!(
+                        tts.get(i + 1),
+                        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis
+                    );
+
+                    if colons >= 2 || dot {
+                        // `crate::app::foo::spawn` is rooted at `foo`
+                        let prefix = match root.as_deref() {
+                            Some("crate") | Some("self") | Some("super") => true,
+                            Some(root) => root == app_name,
+                            None => false,
+                        };
+                        if colons >= 2 && prefix {
+                            root = Some(ident_s.clone());
+                        }
+                    } else {
+                        root = Some(ident_s.clone());
+                    }
+                    let rooted = root
+                        .as_ref()
+                        .map(|root| roots.contains(root))
+                        .unwrap_or(false);
+
+                    let action = match &*ident_s {
+                        "spawn" | "spawn_after" | "spawn_at" | "spawn_with" | "signal" | "call"
+                        | "publish"
+                            if colons >= 2 && rooted =>
+                        {
+                            Some("spawn tasks")
+                        }
+                        "cancel" | "reschedule_after" | "reschedule_at"
+                            if (colons >= 2 || dot) && rooted =>
+                        {
+                            Some("cancel or reschedule tasks")
+                        }
+                        "set_mode" if !dot && is_call => Some("change the mode"),
+                        _ => None,
+                    };
+
+                    if let Some(action) = action {
+                        return Some((ident.span(), action));
+                    }
+
+                    colons = 0;
+                    dot = false;
+                }
+
+                _ => {
+                    root = None;
+                    colons = 0;
+                    dot = false;
+                }
+            }
+        }
+
+        None
+    }
+
+    find(quote!(#(#stmts)*), roots, app_name)
+}
@@ -6,19 +6,25 @@ use crate::{analyze::Analysis, check::Extra};
 
 mod assertions;
 mod dispatchers;
+mod events;
+mod fault;
 mod hardware_tasks;
 mod idle;
+mod in_place;
 mod init;
 mod local_resources;
 mod local_resources_struct;
+mod modes;
 mod module;
 mod post_init;
 mod pre_init;
+mod retained;
 mod shared_resources;
 mod shared_resources_struct;
 mod software_tasks;
+mod task_ids;
 mod timer_queue;
-mod util;
+pub mod util;
 
 pub fn app(app: &App, analysis: &Analysis, extra: &Extra) -> TokenStream2 {
     let mut mod_app = vec![];
@@ -31,19 +37,25 @@ pub fn app(app: &App, analysis: &Analysis, extra: &Extra) -> TokenStream2 {
 
     let pre_init_stmts = pre_init::codegen(app, analysis, extra);
 
+    let (root_pre_init, user_pre_init) = pre_init::hook(extra);
+
     let (mod_app_init, root_init, user_init, call_init) = init::codegen(app, analysis, extra);
 
-    let post_init_stmts = post_init::codegen(app, analysis);
+    let post_init_stmts = post_init::codegen(app, analysis, extra);
 
     let (mod_app_idle, root_idle, user_idle, call_idle) = idle::codegen(app, analysis, extra);
 
     user.push(quote!(
+        #user_pre_init
+
         #user_init
 
         #user_idle
     ));
 
     root.push(quote!(
+        #(#root_pre_init)*
+
         #(#root_init)*
 
         #(#root_idle)*
@@ -94,13 +106,36 @@ pub fn app(app: &App, analysis: &Analysis, extra: &Extra) -> TokenStream2 {
     let (mod_app_software_tasks, root_software_tasks, user_software_tasks) =
         software_tasks::codegen(app, analysis, extra);
 
+    let root_events = events::codegen(app, extra);
+
+    let (root_modes, user_modes) = modes::codegen(app, extra);
+
+    let root_task_ids = task_ids::codegen(app);
+
+    for hook in [
+        &extra.budget_overrun,
+        &extra.on_watchdog_timeout,
+        &extra.watchdog_kick,
+        &extra.fault,
+    ]
+    .iter()
+    .filter_map(|hook| hook.as_ref())
+    {
+        user.push(quote!(
+            #[allow(non_snake_case)]
+            #hook
+        ));
+    }
+
     let mod_app_dispatchers = dispatchers::codegen(app, analysis, extra);
     let mod_app_timer_queue = timer_queue::codegen(app, analysis, extra);
+    let mod_app_fault = fault::codegen(extra);
     let user_imports = &app.user_imports;
     let user_code = &app.user_code;
     let name = &app.name;
     let device = &extra.device;
 
+    let interrupt_free = &util::interrupt_free(extra);
     let monotonic_parts: Vec<_> = app
         .monotonics
         .iter()
@@ -132,7 +167,7 @@ pub fn app(app: &App, analysis: &Analysis, extra: &Extra) -> TokenStream2 {
 
                     /// Read the current time from this monotonic
                     pub fn now() -> rtic::time::Instant<super::super::#name> {
-                        rtic::export::interrupt::free(|_| {
+                        #interrupt_free(|_| {
                             use rtic::Monotonic as _;
                             use rtic::time::Clock as _;
                             if let Some(m) = unsafe{ super::super::#ident.get_mut_unchecked() } {
@@ -185,6 +220,8 @@ pub fn app(app: &App, analysis: &Analysis, extra: &Extra) -> TokenStream2 {
 
             #(#user_software_tasks)*
 
+            #user_modes
+
             #(#root)*
 
             #mod_shared_resources
@@ -195,6 +232,12 @@ pub fn app(app: &App, analysis: &Analysis, extra: &Extra) -> TokenStream2 {
 
             #(#root_software_tasks)*
 
+            #(#root_events)*
+
+            #(#root_modes)*
+
+            #(#root_task_ids)*
+
             /// app module
             #(#mod_app)*
 
@@ -210,6 +253,8 @@ pub fn app(app: &App, analysis: &Analysis, extra: &Extra) -> TokenStream2 {
 
             #(#mod_app_timer_queue)*
 
+            #(#mod_app_fault)*
+
             #(#mains)*
         }
     )
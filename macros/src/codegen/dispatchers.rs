@@ -5,10 +5,11 @@ use rtic_syntax::ast::App;
 use crate::{analyze::Analysis, check::Extra, codegen::util};
 
 /// Generates task dispatchers
-pub fn codegen(app: &App, analysis: &Analysis, _extra: &Extra) -> Vec<TokenStream2> {
+pub fn codegen(app: &App, analysis: &Analysis, extra: &Extra) -> Vec<TokenStream2> {
     let mut items = vec![];
 
     let interrupts = &analysis.interrupts;
+    let index_ty = util::index_ty(app, extra);
 
     for (&level, channel) in &analysis.channels {
         let mut stmts = vec![];
@@ -43,26 +44,74 @@ pub fn codegen(app: &App, analysis: &Analysis, _extra: &Extra) -> Vec<TokenStrea
             }
         ));
 
-        let n = util::capacity_literal(channel.capacity as usize + 1);
-        let rq = util::rq_ident(level);
-        let (rq_ty, rq_expr) = {
-            (
-                quote!(rtic::export::SCRQ<#t, #n>),
-                quote!(rtic::export::Queue::new()),
-            )
-        };
+        // One ready queue per `order`, lower orders first and tasks without one last
+        let mut orders = channel
+            .tasks
+            .iter()
+            .map(|name| extra.orders.get(name).copied())
+            .collect::<Vec<_>>();
+        orders.sort_by_key(|order| order.map_or(256, u16::from));
+        orders.dedup();
 
-        // For future use
-        // let doc = format!(
-        //     "Queue of tasks ready to be dispatched at priority level {}",
-        //     level
-        // );
-        items.push(quote!(
-            #[doc(hidden)]
-            #[allow(non_camel_case_types)]
-            #[allow(non_upper_case_globals)]
-            static #rq: rtic::RacyCell<#rq_ty> = rtic::RacyCell::new(#rq_expr);
-        ));
+        let is_edf = extra.edf_levels.contains(&level);
+        let mut dequeues = vec![];
+        for order in orders {
+            let tasks = channel
+                .tasks
+                .iter()
+                .filter(|name| extra.orders.get(*name).copied() == order)
+                .map(|name| (name, &app.software_tasks[name]))
+                .collect::<Vec<_>>();
+
+            // Each task without inputs may also have one `signal` in the queue
+            let capacity = util::pending_capacity(
+                extra,
+                tasks.iter().map(|(name, task)| (*name, task.args.capacity)),
+            );
+            let signals = tasks
+                .iter()
+                .filter(|(_, task)| task.inputs.is_empty())
+                .count();
+            let n = util::capacity_literal(capacity + signals + 1);
+            let rq = util::rq_ident(level, order);
+            let (rq_ty, rq_expr) = if is_edf {
+                // `check` makes sure there's a default monotonic
+                let mono_type = &util::default_monotonic(app).expect("UNREACHABLE").ty;
+
+                (
+                    quote!(rtic::export::EdfQueue<#mono_type, #t, #index_ty, #n>),
+                    quote!(rtic::export::EdfQueue(
+                        rtic::export::SortedLinkedList::new_u16()
+                    )),
+                )
+            } else {
+                (
+                    quote!(rtic::export::SCRQ<#t, #index_ty, #n>),
+                    quote!(rtic::export::Queue::new()),
+                )
+            };
+
+            // For future use
+            // let doc = format!(
+            //     "Queue of tasks ready to be dispatched at priority level {}",
+            //     level
+            // );
+            items.push(quote!(
+                #[doc(hidden)]
+                #[allow(non_camel_case_types)]
+                #[allow(non_upper_case_globals)]
+                static #rq: rtic::RacyCell<#rq_ty> = rtic::RacyCell::new(#rq_expr);
+            ));
+
+            // Only the spsc queue can be dequeued without a critical section
+            dequeues.push(if is_edf {
+                let interrupt_free = util::interrupt_free(extra);
+
+                quote!(#interrupt_free(|_| #rq.get_mut_unchecked().dequeue()))
+            } else {
+                quote!(#rq.get_mut_unchecked().split().1.dequeue())
+            });
+        }
 
         let arms = channel
             .tasks
@@ -70,32 +119,155 @@ pub fn codegen(app: &App, analysis: &Analysis, _extra: &Extra) -> Vec<TokenStrea
             .map(|name| {
                 let task = &app.software_tasks[name];
                 let cfgs = &task.cfgs;
-                let fq = util::fq_ident(name);
-                let inputs = util::inputs_ident(name);
-                let (_, tupled, pats, _) = util::regroup_inputs(&task.inputs);
+                let fq = util::task_fq_ident(extra, name);
+                let (_, tupled, pats, ty) = util::regroup_inputs(&task.inputs);
+                let slot = util::input_slot(extra, name, &ty, quote!(index));
+                let has_reply = extra.replies.contains_key(name);
+
+                // The reply function must be read before the slot is handed back
+                let read_reply_to = if has_reply {
+                    let reply_to = util::reply_to_ident(name);
+
+                    Some(quote!(
+                        let reply_to = *#reply_to
+                            .get_unchecked()
+                            .get_unchecked(usize::from(index));
+                    ))
+                } else {
+                    None
+                };
+
+                let release = quote!(#fq.get_mut_unchecked().split().0.enqueue_unchecked(index););
+
+                // `by_ref` tasks borrow the message from the buffer, the slot is handed back once
+                // the task returns
+                let (release, drop_message) = if extra.by_ref.contains_key(name) {
+                    (
+                        quote!(
+                            let #tupled = &mut *#slot;
+                            #read_reply_to
+                        ),
+                        Some(quote!(
+                            core::ptr::drop_in_place(#tupled);
+                            #release
+                        )),
+                    )
+                } else {
+                    (
+                        quote!(
+                            let #tupled = #slot.read();
+                            #read_reply_to
+                            #release
+                        ),
+                        None,
+                    )
+                };
+
+                // An unbounded task keeps its entry in the ready queue while it has messages
+                let release = if extra.unbounded.contains(name) {
+                    let queue = util::unbounded_queue_ident(name);
+                    let rq_enqueue = util::rq_enqueue(app, extra, name, quote!(index));
+                    let interrupt_free = util::interrupt_free(extra);
+
+                    quote!(
+                        let node = #interrupt_free(|_| {
+                            let (node, requeue) = #queue.get_mut_unchecked().pop();
+                            if requeue {
+                                #rq_enqueue
+                            }
+
+                            node
+                        });
+                        // The node is freed outside of the critical section
+                        let #tupled = node.into_message();
+                    )
+                } else if task.inputs.is_empty() {
+                    // `signal` doesn't take a slot of the free queue
+                    let signaled = util::signaled_ident(name);
+
+                    if has_reply {
+                        quote!(
+                            let reply_to = if index == #index_ty::MAX {
+                                #signaled.store(false, core::sync::atomic::Ordering::Relaxed);
+                                None
+                            } else {
+                                #release
+                                reply_to
+                            };
+                        )
+                    } else {
+                        quote!(
+                            if index == #index_ty::MAX {
+                                #signaled.store(false, core::sync::atomic::Ordering::Relaxed);
+                            } else {
+                                #release
+                            }
+                        )
+                    }
+                } else {
+                    release
+                };
+
+                let call = quote!(
+                    #name(
+                        #name::Context::new(priority)
+                        #(,#pats)*
+                    )
+                );
+
+                // Replies that don't fit in the queue of the receiver are dropped
+                let call = if has_reply {
+                    quote!(
+                        let reply = #call;
+                        if let Some(reply_to) = reply_to {
+                            let _ = reply_to(reply);
+                        }
+                    )
+                } else {
+                    quote!(#call;)
+                };
+
+                let (budget_start, budget_stop) = util::budget_guard(app, extra, name);
+                let completed = util::watchdog_completed(extra, name);
+                let name_str = name.to_string();
+                let reseal = util::retained_reseal(app, extra, name);
+                let call = quote!(
+                    rtic::export::with_task(#name_str, PRIORITY, || {
+                        #budget_start
+                        #call
+                        #(#reseal)*
+                        #budget_stop
+                        #completed
+                    });
+                );
+
+                // Entries made before a switch to a mode the task may not run in are dropped
+                let call = if let Some(allowed) = util::mode_allowed(extra, name, quote!(mode())) {
+                    quote!(
+                        if #allowed {
+                            #call
+                        }
+                    )
+                } else {
+                    call
+                };
 
                 quote!(
                     #(#cfgs)*
                     #t::#name => {
-                        let #tupled =
-                            #inputs
-                            .get_unchecked()
-                            .get_unchecked(usize::from(index))
-                            .as_ptr()
-                            .read();
-                        #fq.get_mut_unchecked().split().0.enqueue_unchecked(index);
+                        #release
                         let priority = &rtic::export::Priority::new(PRIORITY);
-                        #name(
-                            #name::Context::new(priority)
-                            #(,#pats)*
-                        )
+                        #call
+                        #drop_message
                     }
                 )
             })
             .collect::<Vec<_>>();
 
+        // After each task start over from the first queue
+        let (first, rest) = dequeues.split_first().expect("UNREACHABLE");
         stmts.push(quote!(
-            while let Some((task, index)) = #rq.get_mut_unchecked().split().1.dequeue() {
+            while let Some((task, index)) = #first #(.or_else(|| #rest))* {
                 match task {
                     #(#arms)*
                 }
@@ -0,0 +1,78 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use rtic_syntax::ast::App;
+
+use crate::{check::Extra, codegen::util};
+
+/// Generates the `publish` function of each event
+pub fn codegen(app: &App, extra: &Extra) -> Vec<TokenStream2> {
+    let mut items = vec![];
+
+    for event in &extra.events {
+        let name = &event.ident;
+        let attrs = &event.attrs;
+        let ty = &event.ty;
+        let subscribers = &extra.subscribers[name];
+
+        let internal_message_ident = util::internal_task_ident(name, "Message");
+        let internal_publish_ident = util::internal_task_ident(name, "publish");
+
+        let body = if subscribers.is_empty() {
+            quote!(
+                let _ = message;
+
+                Ok(())
+            )
+        } else {
+            let spawns = subscribers.iter().map(|task| {
+                let cfgs = &app.software_tasks[task].cfgs;
+
+                quote!(
+                    #(#cfgs)*
+                    if #task::spawn(message.clone()).is_err() {
+                        missed += 1;
+                    }
+                )
+            });
+
+            quote!(
+                #[allow(unused_mut)]
+                let mut missed = 0;
+
+                #(#spawns)*
+
+                let _ = message;
+
+                if missed == 0 {
+                    Ok(())
+                } else {
+                    Err(missed)
+                }
+            )
+        };
+
+        items.push(quote!(
+            #[allow(non_camel_case_types)]
+            #[doc(hidden)]
+            pub type #internal_message_ident = #ty;
+
+            /// Spawns every subscriber of the event with a clone of `message`
+            ///
+            /// Subscribers whose queue is full miss the message, their number is returned as the
+            /// error
+            #[allow(non_snake_case)]
+            pub fn #internal_publish_ident(message: #internal_message_ident) -> Result<(), usize> {
+                #body
+            }
+
+            #(#attrs)*
+            #[allow(non_snake_case)]
+            pub mod #name {
+                pub use super::#internal_message_ident as Message;
+                pub use super::#internal_publish_ident as publish;
+            }
+        ));
+    }
+
+    items
+}
@@ -0,0 +1,63 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+
+use crate::{check::Extra, codegen::util};
+
+/// Generates the `HardFault` and panic handlers that call the `#[fault]` function
+pub fn codegen(extra: &Extra) -> Vec<TokenStream2> {
+    let mut items = vec![];
+
+    let fault = if let Some(fault) = &extra.fault {
+        &fault.sig.ident
+    } else {
+        return items;
+    };
+
+    // Set once the `#[fault]` function was called, a panic or a fault inside it must not call it
+    // again. Nothing preempts either handler so a load and a store suffice, ARMv6-M has no swap
+    let reported = util::mark_internal_name("FAULT_REPORTED");
+    let report = |info: TokenStream2| {
+        quote!(
+            if !#reported.load(core::sync::atomic::Ordering::Relaxed) {
+                #reported.store(true, core::sync::atomic::Ordering::Relaxed);
+
+                #fault(rtic::FaultInfo::capture(#info));
+            }
+        )
+    };
+    let report_fault = report(quote!(None));
+    let report_panic = report(quote!(Some(info)));
+
+    items.push(quote!(
+        #[doc(hidden)]
+        #[allow(non_upper_case_globals)]
+        static #reported: core::sync::atomic::AtomicBool =
+            core::sync::atomic::AtomicBool::new(false);
+
+        #[allow(non_snake_case)]
+        #[no_mangle]
+        unsafe fn HardFault() {
+            #report_fault
+
+            // Returning would re-execute the faulting instruction
+            loop {
+                core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+            }
+        }
+
+        #[doc(hidden)]
+        #[panic_handler]
+        fn __rtic_internal_panic(info: &core::panic::PanicInfo) -> ! {
+            // Keep the preemption chain as it is while it's reported
+            rtic::export::interrupt::disable();
+
+            #report_panic
+
+            loop {
+                core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+            }
+        }
+    ));
+
+    items
+}
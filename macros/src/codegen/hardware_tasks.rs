@@ -5,7 +5,7 @@ use rtic_syntax::{ast::App, Context};
 use crate::{
     analyze::Analysis,
     check::Extra,
-    codegen::{local_resources_struct, module, shared_resources_struct},
+    codegen::{local_resources_struct, module, shared_resources_struct, util},
 };
 
 /// Generate support code for hardware tasks (`#[exception]`s and `#[interrupt]`s)
@@ -28,27 +28,142 @@ pub fn codegen(
     let mut root = vec![];
     let mut user_tasks = vec![];
 
+    if !extra.zero_latency.is_empty() {
+        let active = util::zero_latency_active_ident();
+
+        mod_app.push(quote!(
+            rtic::export::assert_zero_latency_supported!();
+
+            #[allow(non_upper_case_globals)]
+            #[doc(hidden)]
+            static #active: core::sync::atomic::AtomicBool =
+                core::sync::atomic::AtomicBool::new(false);
+        ));
+    }
+
     for (name, task) in &app.hardware_tasks {
         let symbol = task.args.binds.clone();
-        let priority = task.args.priority;
+        let priority = util::hardware_task_priority(name, task, extra);
         let cfgs = &task.cfgs;
         let attrs = &task.attrs;
+        let name_str = name.to_string();
+        // The retained resources the task mutated without a lock are sealed once it returns
+        let reseal = util::retained_reseal(app, extra, name);
+
+        if util::is_fixed_priority_exception(&symbol) {
+            // These handlers preempt everything, including the critical sections RTIC uses, so
+            // the task can't touch BASEPRI and only gets access to resources that need no lock
+            let diverge = if &*symbol.to_string() == "HardFault" {
+                // Returning would re-execute the faulting instruction
+                Some(quote!(loop {
+                    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+                }))
+            } else {
+                None
+            };
+
+            mod_app.push(quote!(
+                #[allow(non_snake_case)]
+                #[no_mangle]
+                #(#attrs)*
+                #(#cfgs)*
+                unsafe fn #symbol() {
+                    rtic::export::with_task(#name_str, u8::max_value(), || {
+                        #name(
+                            #name::Context::new(&rtic::export::Priority::new(u8::max_value()))
+                        );
+                        #(#reseal)*
+                    });
+
+                    #diverge
+                }
+            ));
+        } else if extra.zero_latency.contains(name) {
+            // Never writes to BASEPRI; the flag lets `spawn` detect that it's called from here
+            let active = util::zero_latency_active_ident();
 
-        mod_app.push(quote!(
-            #[allow(non_snake_case)]
-            #[no_mangle]
-            #(#attrs)*
-            #(#cfgs)*
-            unsafe fn #symbol() {
-                const PRIORITY: u8 = #priority;
-
-                rtic::export::run(PRIORITY, || {
-                    #name(
-                        #name::Context::new(&rtic::export::Priority::new(PRIORITY))
-                    )
-                });
-            }
-        ));
+            mod_app.push(quote!(
+                #[allow(non_snake_case)]
+                #[no_mangle]
+                #(#attrs)*
+                #(#cfgs)*
+                unsafe fn #symbol() {
+                    const PRIORITY: u8 = #priority;
+
+                    #active.store(true, core::sync::atomic::Ordering::Relaxed);
+                    rtic::export::with_task(#name_str, PRIORITY, || {
+                        #name(
+                            #name::Context::new(&rtic::export::Priority::new(PRIORITY))
+                        );
+                        #(#reseal)*
+                    });
+                    #active.store(false, core::sync::atomic::Ordering::Relaxed);
+                }
+            ));
+        } else {
+            // Rate limited tasks stay masked until the timer queue unmasks them
+            let rate_limit = if let Some((min_interarrival, m)) = extra.rate_limits.get(name) {
+                let t = util::schedule_t_ident();
+                let suppressed = util::suppressed_ident(name);
+                let window = util::rate_limit_window_ident(name);
+                let enqueue = util::tq_enqueue(
+                    app,
+                    extra,
+                    m,
+                    quote!(#t::#name),
+                    quote!(monotonics::#m::now() + #min_interarrival),
+                );
+
+                mod_app.push(quote!(
+                    #(#cfgs)*
+                    #[allow(non_upper_case_globals)]
+                    #[doc(hidden)]
+                    static #suppressed: core::sync::atomic::AtomicU32 =
+                        core::sync::atomic::AtomicU32::new(0);
+
+                    #(#cfgs)*
+                    #[allow(non_upper_case_globals)]
+                    #[doc(hidden)]
+                    static #window: core::sync::atomic::AtomicBool =
+                        core::sync::atomic::AtomicBool::new(false);
+                ));
+
+                Some(quote!(
+                    // `enable` leaves the task masked until the window is over
+                    #window.store(true, core::sync::atomic::Ordering::Relaxed);
+                    #name::disable();
+
+                    #enqueue;
+                ))
+            } else {
+                None
+            };
+
+            let (budget_start, budget_stop) = util::budget_guard(app, extra, name);
+            let completed = util::watchdog_completed(extra, name);
+
+            mod_app.push(quote!(
+                #[allow(non_snake_case)]
+                #[no_mangle]
+                #(#attrs)*
+                #(#cfgs)*
+                unsafe fn #symbol() {
+                    const PRIORITY: u8 = #priority;
+
+                    rtic::export::run(PRIORITY, || rtic::export::with_task(#name_str, PRIORITY, || {
+                        #budget_start
+                        #name(
+                            #name::Context::new(&rtic::export::Priority::new(PRIORITY))
+                        );
+                        #(#reseal)*
+                        #budget_stop
+                        #completed
+                    }));
+
+                    #rate_limit
+                }
+            ));
+        }
 
         let mut shared_needs_lt = false;
         let mut local_needs_lt = false;
@@ -59,6 +174,7 @@ pub fn codegen(
                 Context::HardwareTask(name),
                 &mut local_needs_lt,
                 app,
+                extra,
             );
 
             root.push(item);
@@ -72,6 +188,7 @@ pub fn codegen(
                 Context::HardwareTask(name),
                 &mut shared_needs_lt,
                 app,
+                extra,
             );
 
             root.push(item);
@@ -36,7 +36,7 @@ pub fn codegen(
 
         if !idle.args.shared_resources.is_empty() {
             let (item, constructor) =
-                shared_resources_struct::codegen(Context::Idle, &mut shared_needs_lt, app);
+                shared_resources_struct::codegen(Context::Idle, &mut shared_needs_lt, app, extra);
 
             root_idle.push(item);
             mod_app.push(constructor);
@@ -44,7 +44,7 @@ pub fn codegen(
 
         if !idle.args.local_resources.is_empty() {
             let (item, constructor) =
-                local_resources_struct::codegen(Context::Idle, &mut local_needs_lt, app);
+                local_resources_struct::codegen(Context::Idle, &mut local_needs_lt, app, extra);
 
             root_idle.push(item);
 
@@ -74,9 +74,14 @@ pub fn codegen(
             }
         ));
 
-        let call_idle = quote!(#name(
-            #name::Context::new(&rtic::export::Priority::new(0))
-        ));
+        // `#[idle]` never returns so its frame is never left
+        let name_str = name.to_string();
+        let call_idle = quote!({
+            let frame = rtic::export::TaskFrame::new(#name_str, 0);
+            frame.enter();
+
+            #name(#name::Context::new(&rtic::export::Priority::new(0)))
+        });
 
         (mod_app, root_idle, user_idle, call_idle)
     } else {
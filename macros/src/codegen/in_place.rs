@@ -0,0 +1,70 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use rtic_syntax::ast::App;
+
+use crate::{check::Extra, codegen::util};
+
+/// Generates the `init::Uninit` struct, with the storage of the resources `#[init]` writes in place
+pub fn codegen(app: &App, extra: &Extra) -> Option<TokenStream2> {
+    let shared = app
+        .shared_resources
+        .iter()
+        .filter(|(name, _)| extra.shared_in_place.contains(*name))
+        .map(|(name, res)| {
+            (
+                name,
+                &res.ty,
+                &res.cfgs,
+                util::static_shared_resource_ident(name),
+            )
+        });
+    let local = app
+        .local_resources
+        .iter()
+        .filter(|(name, _)| extra.local_in_place.contains(*name))
+        .map(|(name, res)| {
+            (
+                name,
+                &res.ty,
+                &res.cfgs,
+                util::static_local_resource_ident(name),
+            )
+        });
+
+    let mut fields = vec![];
+    let mut values = vec![];
+    for (name, ty, cfgs, mangled_name) in shared.chain(local) {
+        fields.push(quote!(
+            #(#cfgs)*
+            pub #name: &'static mut core::mem::MaybeUninit<#ty>
+        ));
+
+        values.push(quote!(
+            #(#cfgs)*
+            #name: #mangled_name.get_mut_unchecked()
+        ));
+    }
+
+    if fields.is_empty() {
+        return None;
+    }
+
+    let ident = util::internal_task_ident(&app.init.name, "Uninit");
+    Some(quote!(
+        /// The storage of the resources written in place, `#[init]` returns an `rtic::InPlace`
+        /// for each of them
+        #[allow(non_camel_case_types)]
+        pub struct #ident {
+            #(#fields,)*
+        }
+
+        impl #ident {
+            #[inline(always)]
+            pub unsafe fn new() -> Self {
+                #ident {
+                    #(#values,)*
+                }
+            }
+        }
+    ))
+}
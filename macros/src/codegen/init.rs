@@ -17,7 +17,7 @@ type CodegenResult = (
     // - the `${init}LateResources` struct
     // - the `${init}` module, which contains types like `${init}::Context`
     Vec<TokenStream2>,
-    // user_init -- the `#[init]` function written by the user
+    // user_init -- the `#[init]` function written by the user, and the `#[init_failed]` handler
     TokenStream2,
     // call_init -- the call to the user `#[init]`
     TokenStream2,
@@ -37,11 +37,19 @@ pub fn codegen(app: &App, analysis: &Analysis, extra: &Extra) -> CodegenResult {
     let shared = &init.user_shared_struct;
     let local = &init.user_local_struct;
 
+    // Resources with a constant initializer aren't returned by `#[init]`, the ones written in
+    // place are returned as the proof of the write
     let shared_resources: Vec<_> = app
         .shared_resources
         .iter()
+        .filter(|(k, _)| !extra.shared_inits.contains_key(*k))
         .map(|(k, v)| {
             let ty = &v.ty;
+            let ty = if extra.shared_in_place.contains(k) {
+                quote!(rtic::InPlace<#ty>)
+            } else {
+                quote!(#ty)
+            };
             let cfgs = &v.cfgs;
             quote!(
                 #(#cfgs)*
@@ -52,8 +60,14 @@ pub fn codegen(app: &App, analysis: &Analysis, extra: &Extra) -> CodegenResult {
     let local_resources: Vec<_> = app
         .local_resources
         .iter()
+        .filter(|(k, _)| !extra.local_inits.contains_key(*k))
         .map(|(k, v)| {
             let ty = &v.ty;
+            let ty = if extra.local_in_place.contains(k) {
+                quote!(rtic::InPlace<#ty>)
+            } else {
+                quote!(#ty)
+            };
             let cfgs = &v.cfgs;
             quote!(
                 #(#cfgs)*
@@ -73,32 +87,62 @@ pub fn codegen(app: &App, analysis: &Analysis, extra: &Extra) -> CodegenResult {
 
     // let locals_pat = locals_pat.iter();
 
-    let user_init_return = quote! {#shared, #local, #name::Monotonics};
+    let user_init_return = quote! {(#shared, #local, #name::Monotonics)};
+    let user_init_return = if let Some(error) = &extra.init_error {
+        quote!(Result<#user_init_return, #error>)
+    } else {
+        user_init_return
+    };
 
     let user_init = quote!(
         #(#attrs)*
         #[inline(always)]
         #[allow(non_snake_case)]
-        fn #name(#context: #name::Context) -> (#user_init_return) {
+        fn #name(#context: #name::Context) -> #user_init_return {
             #(#stmts)*
         }
     );
 
+    // The `#[init_failed]` handler, it runs with interrupts still disabled
+    let user_init_failed = extra.init_failed.as_ref().map(|init_failed| {
+        quote!(
+            #[allow(non_snake_case)]
+            #init_failed
+        )
+    });
+
     let mut mod_app = None;
 
     // `${task}Locals`
     if !init.args.local_resources.is_empty() {
         let (item, constructor) =
-            local_resources_struct::codegen(Context::Init, &mut local_needs_lt, app);
+            local_resources_struct::codegen(Context::Init, &mut local_needs_lt, app, extra);
 
         root_init.push(item);
 
         mod_app = Some(constructor);
     }
 
+    let name_str = name.to_string();
+    let call = quote!(rtic::export::with_task(#name_str, 0, || {
+        #name(#name::Context::new(core.into()))
+    }));
+
     // let locals_new = locals_new.iter();
-    let call_init = quote! {
-        let (shared_resources, local_resources, mut monotonics) = #name(#name::Context::new(core.into()));
+    let call_init = if let Some(init_failed) = &extra.init_failed {
+        let on_fail = &init_failed.sig.ident;
+
+        quote! {
+            let (shared_resources, local_resources, mut monotonics) =
+                match #call {
+                    Ok(x) => x,
+                    Err(e) => #on_fail(e),
+                };
+        }
+    } else {
+        quote! {
+            let (shared_resources, local_resources, mut monotonics) = #call;
+        }
     };
 
     root_init.push(module::codegen(
@@ -110,5 +154,11 @@ pub fn codegen(app: &App, analysis: &Analysis, extra: &Extra) -> CodegenResult {
         extra,
     ));
 
+    let user_init = quote!(
+        #user_init
+
+        #user_init_failed
+    );
+
     (mod_app, root_init, user_init, call_init)
 }
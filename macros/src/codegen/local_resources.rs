@@ -10,7 +10,7 @@ use crate::{analyze::Analysis, check::Extra, codegen::util};
 pub fn codegen(
     app: &App,
     _analysis: &Analysis,
-    _extra: &Extra,
+    extra: &Extra,
 ) -> (
     // mod_app -- the `static` variables behind the proxies
     Vec<TokenStream2>,
@@ -27,21 +27,34 @@ pub fn codegen(
         let mangled_name = util::static_local_resource_ident(name);
 
         let attrs = &res.attrs;
-        // late resources in `util::link_section_uninit`
-        let section = util::link_section_uninit();
+        // late resources in `util::link_section_uninit`, the ones with a constant initializer
+        // in `.data` or `.bss`
+        let (section, value) = if let Some(init) = extra.local_inits.get(name) {
+            (None, quote!(core::mem::MaybeUninit::new(#init)))
+        } else {
+            (
+                util::link_section_uninit(),
+                quote!(core::mem::MaybeUninit::uninit()),
+            )
+        };
 
         // For future use
         // let doc = format!(" RTIC internal: {}:{}", file!(), line!());
-        mod_app.push(quote!(
-            #[allow(non_camel_case_types)]
-            #[allow(non_upper_case_globals)]
-            // #[doc = #doc]
-            #[doc(hidden)]
-            #(#attrs)*
-            #(#cfgs)*
-            #section
-            static #mangled_name: rtic::RacyCell<core::mem::MaybeUninit<#ty>> = rtic::RacyCell::new(core::mem::MaybeUninit::uninit());
-        ));
+        // A retained resource keeps the check over its value next to it
+        if extra.local_retained.contains(name) {
+            mod_app.push(util::retained_storage(name, &mangled_name, ty, cfgs, attrs));
+        } else {
+            mod_app.push(quote!(
+                #[allow(non_camel_case_types)]
+                #[allow(non_upper_case_globals)]
+                // #[doc = #doc]
+                #[doc(hidden)]
+                #(#attrs)*
+                #(#cfgs)*
+                #section
+                static #mangled_name: rtic::RacyCell<core::mem::MaybeUninit<#ty>> = rtic::RacyCell::new(#value);
+            ));
+        }
     }
 
     // All declared `local = [NAME: TY = EXPR]` local resources
@@ -53,6 +66,17 @@ pub fn codegen(
 
         let mangled_name = util::declared_static_local_resource_ident(resource_name, &task_name);
 
+        // `#[lazy]` locals are written before `#[init]` runs, their initializer needn't be const
+        let (section, ty, value) = if util::is_lazy_local(extra, &task_name, resource_name) {
+            (
+                util::link_section_uninit(),
+                quote!(core::mem::MaybeUninit<#ty>),
+                quote!(core::mem::MaybeUninit::uninit()),
+            )
+        } else {
+            (None, quote!(#ty), quote!(#expr))
+        };
+
         // For future use
         // let doc = format!(" RTIC internal: {}:{}", file!(), line!());
         mod_app.push(quote!(
@@ -62,7 +86,8 @@ pub fn codegen(
             #[doc(hidden)]
             #(#attrs)*
             #(#cfgs)*
-            static #mangled_name: rtic::RacyCell<#ty> = rtic::RacyCell::new(#expr);
+            #section
+            static #mangled_name: rtic::RacyCell<#ty> = rtic::RacyCell::new(#value);
         ));
     }
 
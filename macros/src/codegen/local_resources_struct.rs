@@ -5,10 +5,15 @@ use rtic_syntax::{
     Context,
 };
 
-use crate::codegen::util;
+use crate::{check::Extra, codegen::util};
 
 /// Generates local resources structs
-pub fn codegen(ctxt: Context, needs_lt: &mut bool, app: &App) -> (TokenStream2, TokenStream2) {
+pub fn codegen(
+    ctxt: Context,
+    needs_lt: &mut bool,
+    app: &App,
+    extra: &Extra,
+) -> (TokenStream2, TokenStream2) {
     let mut lt = None;
 
     let resources = match ctxt {
@@ -54,7 +59,7 @@ pub fn codegen(ctxt: Context, needs_lt: &mut bool, app: &App) -> (TokenStream2,
             pub #name: &#lt mut #ty
         ));
 
-        let expr = if is_declared {
+        let expr = if is_declared && !util::is_lazy_local(extra, &task_name, name) {
             // If the local resources is already initialized, we only need to access its value and
             // not go through an `MaybeUninit`
             quote!(#mangled_name.get_mut_unchecked())
@@ -0,0 +1,170 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use rtic_syntax::ast::App;
+
+use crate::{check::Extra, codegen::util};
+
+/// Generates the `Mode` enum, `mode` and `set_mode`
+pub fn codegen(
+    app: &App,
+    extra: &Extra,
+) -> (
+    // root_modes -- the `Mode` enum and the functions to read and switch it
+    Vec<TokenStream2>,
+    // user_modes -- the `#[on_mode_change]` function written by the user
+    Option<TokenStream2>,
+) {
+    let mut items = vec![];
+
+    if extra.modes.is_empty() {
+        return (items, None);
+    }
+
+    let modes = &extra.modes;
+    let (last, others) = modes.split_last().expect("UNREACHABLE");
+    let indices = (0..others.len()).map(|i| i as u8);
+    let mode = util::mode_ident();
+    let interrupt_free = util::interrupt_free(extra);
+    let unmaskable_guard = util::unmaskable_guard(app, extra, quote!(()));
+
+    let mut stmts = vec![];
+
+    // Mask the hardware tasks that may not run in the next mode, unmask the ones that now may
+    for (name, task) in &app.hardware_tasks {
+        let cfgs = &task.cfgs;
+
+        if let (Some(before), Some(after)) = (
+            util::mode_allowed(extra, name, quote!(previous)),
+            util::mode_allowed(extra, name, quote!(next)),
+        ) {
+            stmts.push(quote!(
+                #(#cfgs)*
+                match (#before, #after) {
+                    (true, false) => #name::disable(),
+                    (false, true) => #name::enable(),
+                    _ => {}
+                }
+            ));
+        }
+    }
+
+    // Cancel the scheduled software tasks that may not run in the next mode, unbounded tasks are
+    // never scheduled
+    let t = util::schedule_t_ident();
+    let (cancels, frees): (Vec<_>, Vec<_>) = app
+        .software_tasks
+        .iter()
+        .filter(|(name, _)| !extra.unbounded.contains(*name))
+        .filter_map(|(name, task)| {
+            let allowed = util::mode_allowed(extra, name, quote!(next))?;
+            let cfgs = &task.cfgs;
+            let fq = util::task_fq_ident(extra, name);
+            let (_, _, _, ty) = util::regroup_inputs(&task.inputs);
+            let slot = util::input_slot(extra, name, &ty, quote!(index));
+
+            Some((
+                quote!(
+                    #(#cfgs)*
+                    #t::#name => !#allowed,
+                ),
+                quote!(
+                    #(#cfgs)*
+                    #t::#name => {
+                        let _ = #slot.read();
+                        #fq.get_mut_unchecked().split().0.enqueue_unchecked(index);
+                    }
+                ),
+            ))
+        })
+        .unzip();
+
+    if !cancels.is_empty() {
+        for (_, monotonic) in &app.monotonics {
+            let tq = util::tq_ident(&monotonic.ident.to_string());
+
+            stmts.push(quote!(
+                unsafe {
+                    while let Some((task, index)) = #tq.get_mut_unchecked().cancel_task(|task| {
+                        match task {
+                            #(#cancels)*
+                            #[allow(unreachable_patterns)]
+                            _ => false,
+                        }
+                    }) {
+                        match task {
+                            #(#frees)*
+                            #[allow(unreachable_patterns)]
+                            _ => {}
+                        }
+                    }
+                }
+            ));
+        }
+    }
+
+    let (call_hook, user) = if let Some(hook) = &extra.on_mode_change {
+        let name = &hook.sig.ident;
+
+        (
+            Some(quote!(
+                if previous != next {
+                    #name(previous, next);
+                }
+            )),
+            Some(quote!(
+                #[allow(non_snake_case)]
+                #hook
+            )),
+        )
+    } else {
+        (None, None)
+    };
+
+    items.push(quote!(
+        /// The operating modes of the application
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum Mode {
+            #(#modes,)*
+        }
+
+        #[doc(hidden)]
+        #[allow(non_upper_case_globals)]
+        static #mode: core::sync::atomic::AtomicU8 = core::sync::atomic::AtomicU8::new(0);
+
+        /// The current operating mode
+        pub fn mode() -> Mode {
+            match #mode.load(core::sync::atomic::Ordering::Relaxed) {
+                #(#indices => Mode::#others,)*
+                _ => Mode::#last,
+            }
+        }
+
+        /// Switches to the `next` operating mode
+        ///
+        /// Hardware tasks that may not run in `next` are masked and the ones that now may are
+        /// unmasked. Spawning a software task that may not run in `next` fails, its scheduled
+        /// and pending runs are canceled
+        ///
+        /// Does nothing when called from a task RTIC can't mask, e.g. `NonMaskableInt`
+        pub fn set_mode(next: Mode) {
+            #unmaskable_guard
+
+            #[allow(unused_variables)]
+            let previous = #interrupt_free(|_| {
+                let previous = mode();
+
+                if previous != next {
+                    #mode.store(next as u8, core::sync::atomic::Ordering::Relaxed);
+
+                    #(#stmts)*
+                }
+
+                previous
+            });
+
+            #call_hook
+        }
+    ));
+
+    (items, user)
+}
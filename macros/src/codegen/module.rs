@@ -1,4 +1,8 @@
-use crate::{analyze::Analysis, check::Extra, codegen::util};
+use crate::{
+    analyze::Analysis,
+    check::Extra,
+    codegen::{in_place, retained, task_ids, util},
+};
 use proc_macro2::TokenStream as TokenStream2;
 use quote::quote;
 use rtic_syntax::{ast::App, Context};
@@ -47,6 +51,40 @@ pub fn codegen(
 
             values.push(quote!(cs: rtic::export::CriticalSection::new()));
 
+            if let Some(retained) = retained::codegen(app, extra) {
+                let ident = util::internal_task_ident(name, "Retained");
+                items.push(retained);
+
+                fields.push(quote!(
+                    /// Retained resources, as they were before the reset
+                    pub retained: #ident
+                ));
+
+                values.push(quote!(retained: #ident::new()));
+
+                module_items.push(quote!(
+                    #[doc(inline)]
+                    pub use super::#ident as Retained;
+                ));
+            }
+
+            if let Some(uninit) = in_place::codegen(app, extra) {
+                let ident = util::internal_task_ident(name, "Uninit");
+                items.push(uninit);
+
+                fields.push(quote!(
+                    /// Storage of the resources written in place
+                    pub uninit: #ident
+                ));
+
+                values.push(quote!(uninit: #ident::new()));
+
+                module_items.push(quote!(
+                    #[doc(inline)]
+                    pub use super::#ident as Uninit;
+                ));
+            }
+
             values.push(quote!(core));
         }
 
@@ -199,19 +237,184 @@ pub fn codegen(
         pub use super::#internal_context_name as Context;
     ));
 
+    let task_priority = util::task_priority(ctxt, app, extra);
+    let metadata = task_ids::metadata(ctxt, app, analysis, extra);
+    module_items.push(quote!(
+        #(#cfgs)*
+        /// The priority the task runs at
+        pub const PRIORITY: u8 = #task_priority;
+
+        #(#cfgs)*
+        /// Compile time description of the task
+        pub const METADATA: rtic::TaskMetadata = #metadata;
+    ));
+
+    if let Context::HardwareTask(..) = ctxt {
+        let task = &app.hardware_tasks[name];
+        let cfgs = &task.cfgs;
+        let binds = &task.args.binds;
+        let rt_err = util::rt_err_ident();
+        let enum_ = util::interrupt_ident();
+
+        // How to mask, unmask and query the handler the task is bound to. Only the exceptions that
+        // can be disabled are covered
+        let control = match &*binds.to_string() {
+            "SysTick" => Some((
+                quote!(unsafe {
+                    core::mem::transmute::<_, rtic::export::SYST>(()).disable_interrupt()
+                }),
+                quote!(unsafe {
+                    core::mem::transmute::<_, rtic::export::SYST>(()).enable_interrupt()
+                }),
+                quote!(unsafe {
+                    core::mem::transmute::<_, rtic::export::SYST>(()).is_interrupt_enabled()
+                }),
+            )),
+
+            "MemoryManagement" | "BusFault" | "UsageFault" | "SecureFault" => {
+                // Enable bits of these handlers in the SHCSR register
+                let bit: u32 = match &*binds.to_string() {
+                    "MemoryManagement" => 16,
+                    "BusFault" => 17,
+                    "UsageFault" => 18,
+                    _ => 19,
+                };
+
+                let interrupt_free = util::interrupt_free(extra);
+
+                Some((
+                    quote!(#interrupt_free(|_| unsafe {
+                        rtic::export::set_system_handler_enabled(#bit, false)
+                    })),
+                    quote!(#interrupt_free(|_| unsafe {
+                        rtic::export::set_system_handler_enabled(#bit, true)
+                    })),
+                    quote!(rtic::export::is_system_handler_enabled(#bit)),
+                ))
+            }
+
+            _ if util::is_exception(binds) || util::is_fixed_priority_exception(binds) => None,
+
+            _ => Some((
+                quote!(rtic::export::NVIC::mask(#rt_err::#enum_::#binds)),
+                quote!(unsafe { rtic::export::NVIC::unmask(#rt_err::#enum_::#binds) }),
+                quote!(rtic::export::NVIC::is_enabled(#rt_err::#enum_::#binds)),
+            )),
+        };
+
+        if let Some((disable, enable, is_enabled)) = control {
+            // Don't unmask a task that may not run in the current mode or that is masked to
+            // respect its `min_interarrival`
+            let mut conditions = vec![];
+            if let Some(allowed) = util::mode_allowed(extra, name, quote!(mode())) {
+                conditions.push(allowed);
+            }
+            if extra.rate_limits.contains_key(name) {
+                let window = util::rate_limit_window_ident(name);
+                conditions.push(quote!(!#window.load(core::sync::atomic::Ordering::Relaxed)));
+            }
+            let enable = if conditions.is_empty() {
+                enable
+            } else {
+                let interrupt_free = util::interrupt_free(extra);
+
+                quote!(#interrupt_free(|_| {
+                    if #(#conditions)&&* {
+                        #enable
+                    }
+                }))
+            };
+
+            // Disabling these faults doesn't mask them, they escalate to `HardFault` instead
+            let escalates = if matches!(
+                &*binds.to_string(),
+                "MemoryManagement" | "BusFault" | "UsageFault"
+            ) {
+                Some(quote!(
+                    ///
+                    /// The fault isn't masked by this: while the handler is disabled the fault
+                    /// escalates to `HardFault`
+                ))
+            } else {
+                None
+            };
+
+            let internal_disable_ident = util::internal_task_ident(name, "disable");
+            let internal_enable_ident = util::internal_task_ident(name, "enable");
+            let internal_is_enabled_ident = util::internal_task_ident(name, "is_enabled");
+
+            items.push(quote!(
+                #(#cfgs)*
+                /// Masks the handler of the task, it won't run until it's enabled again
+                #escalates
+                #[allow(non_snake_case)]
+                pub fn #internal_disable_ident() {
+                    #disable
+                }
+
+                #(#cfgs)*
+                /// Unmasks the handler of the task
+                ///
+                /// The handler stays masked if the task may not run in the current mode, or until
+                /// the end of its `min_interarrival` window
+                #[allow(non_snake_case)]
+                pub fn #internal_enable_ident() {
+                    #enable
+                }
+
+                #(#cfgs)*
+                /// Whether the handler of the task is enabled
+                #[allow(non_snake_case)]
+                pub fn #internal_is_enabled_ident() -> bool {
+                    #is_enabled
+                }
+            ));
+
+            module_items.push(quote!(
+                #(#cfgs)*
+                pub use super::#internal_disable_ident as disable;
+                #(#cfgs)*
+                pub use super::#internal_enable_ident as enable;
+                #(#cfgs)*
+                pub use super::#internal_is_enabled_ident as is_enabled;
+            ));
+        }
+
+        if extra.rate_limits.contains_key(name) {
+            let suppressed = util::suppressed_ident(name);
+            let internal_suppressed_ident = util::internal_task_ident(name, "suppressed");
+
+            items.push(quote!(
+                #(#cfgs)*
+                /// How many times the interrupt fired while the task was masked to respect its
+                /// `min_interarrival`
+                ///
+                /// Several activations in one window count once, the interrupt controller keeps a
+                /// single one pending. The pending activation runs the task once it's unmasked
+                #[allow(non_snake_case)]
+                pub fn #internal_suppressed_ident() -> u32 {
+                    #suppressed.load(core::sync::atomic::Ordering::Relaxed)
+                }
+            ));
+
+            module_items.push(quote!(
+                #(#cfgs)*
+                pub use super::#internal_suppressed_ident as suppressed;
+            ));
+        }
+    }
+
     if let Context::SoftwareTask(..) = ctxt {
         let spawnee = &app.software_tasks[name];
         let priority = spawnee.args.priority;
-        let t = util::spawn_t_ident(priority);
         let cfgs = &spawnee.cfgs;
         // Store a copy of the task cfgs
         task_cfgs = cfgs.clone();
         let (args, tupled, untupled, ty) = util::regroup_inputs(&spawnee.inputs);
         let args = &args;
         let tupled = &tupled;
-        let fq = util::fq_ident(name);
-        let rq = util::rq_ident(priority);
-        let inputs = util::inputs_ident(name);
+        let fq = util::task_fq_ident(extra, name);
+        let slot = util::input_slot(extra, name, &ty, quote!(index));
 
         let device = &extra.device;
         let enum_ = util::interrupt_ident();
@@ -223,43 +426,240 @@ pub fn codegen(
 
         let internal_spawn_ident = util::internal_task_ident(name, "spawn");
 
-        // Spawn caller
+        let interrupt_free = &util::interrupt_free(extra);
+        let unmaskable_guard = &util::unmaskable_guard(app, extra, quote!(Err(input)));
+        let unmaskable_handle_guard = &util::unmaskable_guard(app, extra, quote!(Err(())));
+        let mode_guard = &util::mode_spawn_guard(extra, name);
+
+        // Remembers which task `call` delivers the reply to, `spawn` leaves it empty
+        let reply = extra.replies.get(name);
+        let reply_to = util::reply_to_ident(name);
+        let write_reply_to = |value: TokenStream2| {
+            reply.map(|_| {
+                quote!(
+                    *#reply_to
+                        .get_mut_unchecked()
+                        .get_unchecked_mut(usize::from(index)) = #value;
+                )
+            })
+        };
+
+        let rq_enqueue = util::rq_enqueue(app, extra, name, quote!(index));
+        let spawn_body = |write_reply_to: Option<TokenStream2>| {
+            quote!(
+                let input = #tupled;
+
+                #unmaskable_guard
+
+                #mode_guard
+
+                unsafe {
+                    if let Some(index) = #interrupt_free(|_| #fq.get_mut_unchecked().dequeue()) {
+                        #slot.write(input);
+
+                        #write_reply_to
+
+                        #interrupt_free(|_| {
+                            #rq_enqueue
+                        });
+
+                        rtic::pend(#device::#enum_::#interrupt);
+
+                        Ok(())
+                    } else {
+                        Err(input)
+                    }
+                }
+            )
+        };
+
+        // Spawn caller, unbounded tasks put the message on the heap and have a single entry in the
+        // ready queue however many messages are pending
+        let unbounded = extra.unbounded.contains(name);
+        let body = if unbounded {
+            let queue = util::unbounded_queue_ident(name);
+            let rq_enqueue = util::rq_enqueue(app, extra, name, quote!(0));
+
+            quote!(
+                let input = #tupled;
+
+                #unmaskable_guard
+
+                #mode_guard
+
+                // The message is moved to the heap before the critical section, which only links
+                // it and adds the entry in the ready queue; the dispatcher takes them out at once
+                let node = match rtic::export::UnboundedNode::new(input) {
+                    Ok(node) => node,
+                    Err(input) => return Err(input),
+                };
+
+                #interrupt_free(|_| unsafe {
+                    if #queue.get_mut_unchecked().push(node) {
+                        #rq_enqueue
+                    }
+                });
+
+                rtic::pend(#device::#enum_::#interrupt);
+
+                Ok(())
+            )
+        } else {
+            spawn_body(write_reply_to(quote!(None)))
+        };
         items.push(quote!(
 
         #(#cfgs)*
         /// Spawns the task directly
         pub fn #internal_spawn_ident(#(#args,)*) -> Result<(), #ty> {
-            let input = #tupled;
-
-            unsafe {
-                if let Some(index) = rtic::export::interrupt::free(|_| #fq.get_mut_unchecked().dequeue()) {
-                    #inputs
-                        .get_mut_unchecked()
-                        .get_unchecked_mut(usize::from(index))
-                        .as_mut_ptr()
-                        .write(input);
-
-                    rtic::export::interrupt::free(|_| {
-                        #rq.get_mut_unchecked().enqueue_unchecked((#t::#name, index));
-                    });
+            #body
+        }));
 
-                    rtic::pend(#device::#enum_::#interrupt);
+        // Call caller, only for tasks that return a reply
+        if let Some(reply) = reply {
+            let internal_call_ident = util::internal_task_ident(name, "call");
+            let body = spawn_body(write_reply_to(quote!(Some(reply_to))));
 
-                    Ok(())
-                } else {
-                    Err(input)
+            items.push(quote!(
+                #(#cfgs)*
+                /// Spawns the task, its return value is then passed to `reply_to`
+                ///
+                /// `reply_to` is usually the `spawn` function of another task; the reply is
+                /// dropped if that fails
+                pub fn #internal_call_ident(
+                    #(#args,)*
+                    reply_to: fn(#reply) -> Result<(), #reply>,
+                ) -> Result<(), #ty> {
+                    #body
                 }
-            }
+            ));
 
-        }));
+            module_items.push(quote!(
+                #(#cfgs)*
+                pub use super::#internal_call_ident as call;
+            ));
+        }
 
         module_items.push(quote!(
             #(#cfgs)*
             pub use super::#internal_spawn_ident as spawn;
         ));
 
-        // Schedule caller
-        for (_, monotonic) in &app.monotonics {
+        // In place spawn, only for tasks that take inputs into a slot
+        if !spawnee.inputs.is_empty() && !unbounded {
+            let internal_spawn_with_ident = util::internal_task_ident(name, "spawn_with");
+            let write_reply_to = write_reply_to(quote!(None));
+            let msg = format!(
+                "`{}::spawn_with` must return the slot it's given, initialized",
+                name
+            );
+
+            items.push(quote!(
+                #(#cfgs)*
+                /// Spawns the task, `write` initializes the message directly in the buffer of the
+                /// task and returns the slot it was given
+                ///
+                /// `write` isn't called when the task can't be spawned, it's returned instead
+                ///
+                /// # Panics
+                ///
+                /// If `write` returns a reference to anything but the slot it was given
+                pub fn #internal_spawn_with_ident<F>(write: F) -> Result<(), F>
+                where
+                    F: for<'a> FnOnce(&'a mut core::mem::MaybeUninit<#ty>) -> &'a mut #ty,
+                {
+                    let input = write;
+
+                    #unmaskable_guard
+
+                    #mode_guard
+
+                    unsafe {
+                        if let Some(index) = #interrupt_free(|_| #fq.get_mut_unchecked().dequeue()) {
+                            let expected = #slot;
+
+                            // A `&mut` to the slot can only be returned once it's initialized
+                            let slot = &mut *(expected as *mut core::mem::MaybeUninit<#ty>);
+                            if !core::ptr::eq(input(slot), expected) {
+                                // The slot is released before panicking, a `#[fault]` handler
+                                // that doesn't reset may keep spawning the task
+                                #interrupt_free(|_| {
+                                    #fq.get_mut_unchecked().split().0.enqueue_unchecked(index);
+                                });
+
+                                panic!(#msg);
+                            }
+
+                            #write_reply_to
+
+                            #interrupt_free(|_| {
+                                #rq_enqueue
+                            });
+
+                            rtic::pend(#device::#enum_::#interrupt);
+
+                            Ok(())
+                        } else {
+                            Err(input)
+                        }
+                    }
+                }
+            ));
+
+            module_items.push(quote!(
+                #(#cfgs)*
+                pub use super::#internal_spawn_with_ident as spawn_with;
+            ));
+        }
+
+        // Signal caller, only for tasks that take no input
+        if spawnee.inputs.is_empty() {
+            let signaled = util::signaled_ident(name);
+            let internal_signal_ident = util::internal_task_ident(name, "signal");
+            let index_ty = util::index_ty(app, extra);
+            let signal_enqueue = util::rq_enqueue(app, extra, name, quote!(#index_ty::MAX));
+
+            items.push(quote!(
+                #(#cfgs)*
+                /// Spawns the task unless it's already pending from an earlier `signal`
+                ///
+                /// Signals don't use the capacity of the task: however many times the task is
+                /// signaled before it runs, it runs once
+                pub fn #internal_signal_ident() -> Result<(), ()> {
+                    let input = ();
+
+                    #unmaskable_guard
+
+                    #mode_guard
+
+                    if #signaled.load(core::sync::atomic::Ordering::Relaxed) {
+                        return Ok(());
+                    }
+
+                    unsafe {
+                        #interrupt_free(|_| {
+                            // Check again, we may have been preempted by another `signal`
+                            if !#signaled.load(core::sync::atomic::Ordering::Relaxed) {
+                                #signaled.store(true, core::sync::atomic::Ordering::Relaxed);
+                                #signal_enqueue
+                            }
+                        });
+                    }
+
+                    rtic::pend(#device::#enum_::#interrupt);
+
+                    Ok(input)
+                }
+            ));
+
+            module_items.push(quote!(
+                #(#cfgs)*
+                pub use super::#internal_signal_ident as signal;
+            ));
+        }
+
+        // Schedule caller, unbounded tasks can only be spawned right away
+        for (_, monotonic) in app.monotonics.iter().filter(|_| !unbounded) {
             let instants = util::monotonic_instants_ident(name, &monotonic.ident);
             let monotonic_name = monotonic.ident.to_string();
 
@@ -268,24 +668,12 @@ pub fn codegen(
             let m = &monotonic.ident;
             let mono_type = &monotonic.ident;
             let m_ident = util::monotonic_ident(&monotonic_name);
-            let m_isr = &monotonic.args.binds;
-            let enum_ = util::interrupt_ident();
             let spawn_handle_string = format!("{}::SpawnHandle", m.to_string());
 
-            let (enable_interrupt, pend) = if &*m_isr.to_string() == "SysTick" {
-                (
-                    quote!(core::mem::transmute::<_, rtic::export::SYST>(()).enable_interrupt()),
-                    quote!(rtic::export::SCB::set_pendst()),
-                )
-            } else {
-                let rt_err = util::rt_err_ident();
-                (
-                    quote!(rtic::export::NVIC::unmask(#rt_err::#enum_::#m_isr)),
-                    quote!(rtic::pend(#rt_err::#enum_::#m_isr)),
-                )
-            };
+            let (enable_interrupt, pend) = util::monotonic_interrupt(monotonic);
 
             let tq_marker = &util::timer_queue_marker_ident();
+            let clear_reply_to = write_reply_to(quote!(None));
 
             // For future use
             // let doc = format!(" RTIC internal: {}:{}", file!(), line!());
@@ -329,15 +717,13 @@ pub fn codegen(
                 #(#cfgs)*
                 impl #internal_spawn_handle_ident {
                     pub fn cancel(self) -> Result<#ty, ()> {
-                        rtic::export::interrupt::free(|_| unsafe {
+                        #unmaskable_handle_guard
+
+                        #interrupt_free(|_| unsafe {
                             let tq = #tq.get_mut_unchecked();
                             if let Some((_task, index)) = tq.cancel_marker(self.marker) {
                                 // Get the message
-                                let msg = #inputs
-                                    .get_unchecked()
-                                    .get_unchecked(usize::from(index))
-                                    .as_ptr()
-                                    .read();
+                                let msg = #slot.read();
                                 // Return the index to the free queue
                                 #fq.get_mut_unchecked().split().0.enqueue_unchecked(index);
 
@@ -358,7 +744,9 @@ pub fn codegen(
 
                     pub fn reschedule_at(self, instant: rtic::time::Instant<#mono_type>) -> Result<Self, ()>
                     {
-                        rtic::export::interrupt::free(|_| unsafe {
+                        #unmaskable_handle_guard
+
+                        #interrupt_free(|_| unsafe {
                             let marker = *#tq_marker.get_mut_unchecked();
                             *#tq_marker.get_mut_unchecked() = #tq_marker.get_mut_unchecked().wrapping_add(1);
 
@@ -383,7 +771,7 @@ pub fn codegen(
                         D::T: Into<<#mono_type as rtic::time::Clock>::T>,
                 {
 
-                    let instant = if rtic::export::interrupt::free(|_| unsafe { #m_ident.get_mut_unchecked().is_none() }) {
+                    let instant = if #interrupt_free(|_| unsafe { #m_ident.get_mut_unchecked().is_none() }) {
                         rtic::time::Instant::new(0)
                     } else {
                         monotonics::#m::now()
@@ -401,12 +789,15 @@ pub fn codegen(
                 ) -> Result<#name::#m::SpawnHandle, #ty> {
                     unsafe {
                         let input = #tupled;
-                        if let Some(index) = rtic::export::interrupt::free(|_| #fq.get_mut_unchecked().dequeue()) {
-                            #inputs
-                                .get_mut_unchecked()
-                                .get_unchecked_mut(usize::from(index))
-                                .as_mut_ptr()
-                                .write(input);
+
+                        #unmaskable_guard
+
+                        #mode_guard
+
+                        if let Some(index) = #interrupt_free(|_| #fq.get_mut_unchecked().dequeue()) {
+                            #slot.write(input);
+
+                            #clear_reply_to
 
                             #instants
                                 .get_mut_unchecked()
@@ -414,7 +805,7 @@ pub fn codegen(
                                 .as_mut_ptr()
                                 .write(instant);
 
-                            rtic::export::interrupt::free(|_| {
+                            #interrupt_free(|_| {
                                 let marker = *#tq_marker.get_mut_unchecked();
                                 let nr = rtic::export::NotReady {
                                     instant,
@@ -1,47 +1,77 @@
 use proc_macro2::{Span, TokenStream as TokenStream2};
 use quote::quote;
 use rtic_syntax::ast::App;
-use syn::Index;
+use syn::{Attribute, Ident, Index};
 
-use crate::{analyze::Analysis, codegen::util};
+use crate::{analyze::Analysis, check::Extra, codegen::util};
 
 /// Generates code that runs after `#[init]` returns
-pub fn codegen(app: &App, analysis: &Analysis) -> Vec<TokenStream2> {
+pub fn codegen(app: &App, analysis: &Analysis, extra: &Extra) -> Vec<TokenStream2> {
     let mut stmts = vec![];
 
-    // Initialize shared resources
+    // Initialize shared resources, except the ones with a constant initializer
     for (name, res) in &app.shared_resources {
         let mangled_name = util::static_shared_resource_ident(name);
         // If it's live
         let cfgs = res.cfgs.clone();
-        if analysis.shared_resource_locations.get(name).is_some() {
-            stmts.push(quote!(
-                // We include the cfgs
-                #(#cfgs)*
-                // Resource is a RacyCell<MaybeUninit<T>>
-                // - `get_mut_unchecked` to obtain `MaybeUninit<T>`
-                // - `as_mut_ptr` to obtain a raw pointer to `MaybeUninit<T>`
-                // - `write` the defined value for the late resource T
-                #mangled_name.get_mut_unchecked().as_mut_ptr().write(shared_resources.#name);
-            ));
+        if analysis.shared_resource_locations.get(name).is_some()
+            && !extra.shared_inits.contains_key(name)
+        {
+            if extra.shared_in_place.contains(name) {
+                stmts.push(in_place_check(
+                    name,
+                    &mangled_name,
+                    quote!(shared_resources),
+                    &cfgs,
+                ));
+            } else {
+                stmts.push(quote!(
+                    // We include the cfgs
+                    #(#cfgs)*
+                    // Resource is a RacyCell<MaybeUninit<T>>
+                    // - `get_mut_unchecked` to obtain `MaybeUninit<T>`
+                    // - `as_mut_ptr` to obtain a raw pointer to `MaybeUninit<T>`
+                    // - `write` the defined value for the late resource T
+                    #mangled_name.get_mut_unchecked().as_mut_ptr().write(shared_resources.#name);
+                ));
+            }
+
+            if extra.shared_retained.contains(name) {
+                stmts.push(util::retained_seal(name, &mangled_name, &cfgs));
+            }
         }
     }
 
-    // Initialize local resources
+    // Initialize local resources, except the ones with a constant initializer
     for (name, res) in &app.local_resources {
         let mangled_name = util::static_local_resource_ident(name);
         // If it's live
         let cfgs = res.cfgs.clone();
-        if analysis.local_resource_locations.get(name).is_some() {
-            stmts.push(quote!(
-                // We include the cfgs
-                #(#cfgs)*
-                // Resource is a RacyCell<MaybeUninit<T>>
-                // - `get_mut_unchecked` to obtain `MaybeUninit<T>`
-                // - `as_mut_ptr` to obtain a raw pointer to `MaybeUninit<T>`
-                // - `write` the defined value for the late resource T
-                #mangled_name.get_mut_unchecked().as_mut_ptr().write(local_resources.#name);
-            ));
+        if analysis.local_resource_locations.get(name).is_some()
+            && !extra.local_inits.contains_key(name)
+        {
+            if extra.local_in_place.contains(name) {
+                stmts.push(in_place_check(
+                    name,
+                    &mangled_name,
+                    quote!(local_resources),
+                    &cfgs,
+                ));
+            } else {
+                stmts.push(quote!(
+                    // We include the cfgs
+                    #(#cfgs)*
+                    // Resource is a RacyCell<MaybeUninit<T>>
+                    // - `get_mut_unchecked` to obtain `MaybeUninit<T>`
+                    // - `as_mut_ptr` to obtain a raw pointer to `MaybeUninit<T>`
+                    // - `write` the defined value for the late resource T
+                    #mangled_name.get_mut_unchecked().as_mut_ptr().write(local_resources.#name);
+                ));
+            }
+
+            if extra.local_retained.contains(name) {
+                stmts.push(util::retained_seal(name, &mangled_name, &cfgs));
+            }
         }
     }
 
@@ -61,8 +91,64 @@ pub fn codegen(app: &App, analysis: &Analysis) -> Vec<TokenStream2> {
         stmts.push(quote!(*#name.get_mut_unchecked() = Some(monotonics.#idx);));
     }
 
+    // Open the first window of the tasks supervised by the watchdog
+    for (name, (window, m)) in &extra.watchdogs {
+        let cfgs = util::task_cfgs(app, name);
+        let t = util::schedule_t_ident();
+        let variant = util::watchdog_variant_ident(name);
+        let enqueue = util::tq_enqueue(
+            app,
+            extra,
+            m,
+            quote!(#t::#variant),
+            quote!(monotonics::#m::now() + #window),
+        );
+
+        stmts.push(quote!(
+            #(#cfgs)*
+            #enqueue;
+        ));
+    }
+
+    // Mask the hardware tasks that may not run in the initial mode, the first one declared
+    if let Some(initial) = extra.modes.first() {
+        for (name, task) in &app.hardware_tasks {
+            let cfgs = &task.cfgs;
+
+            if let Some(modes) = extra.task_modes.get(name) {
+                if !modes.contains(initial) {
+                    stmts.push(quote!(
+                        #(#cfgs)*
+                        #name::disable();
+                    ));
+                }
+            }
+        }
+    }
+
     // Enable the interrupts -- this completes the `init`-ialization phase
     stmts.push(quote!(rtic::export::interrupt::enable();));
 
     stmts
 }
+
+// Checks that in place resource `name` was written to its own storage, `cx.uninit.<name>`
+fn in_place_check(
+    name: &Ident,
+    mangled_name: &Ident,
+    resources: TokenStream2,
+    cfgs: &[Attribute],
+) -> TokenStream2 {
+    let msg = format!(
+        "`{}` must be written in place, through `cx.uninit.{}`",
+        name, name
+    );
+
+    quote!(
+        #(#cfgs)*
+        assert!(
+            core::ptr::eq(#resources.#name.as_ptr(), #mangled_name.get_unchecked().as_ptr()),
+            #msg
+        );
+    )
+}
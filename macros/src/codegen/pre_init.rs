@@ -13,9 +13,44 @@ pub fn codegen(app: &App, analysis: &Analysis, extra: &Extra) -> Vec<TokenStream
     // Disable interrupts -- `init` must run with interrupts disabled
     stmts.push(quote!(rtic::export::interrupt::disable();));
 
-    // Populate the FreeQueue
+    stmts.push(quote!(
+        // To set the variable in cortex_m so the peripherals cannot be taken multiple times
+        let mut core: rtic::export::Peripherals = rtic::export::Peripherals::steal().into();
+    ));
+
+    // The user `#[pre_init]` runs before RTIC writes to any of its `static`s
+    if let Some(pre_init) = &extra.pre_init {
+        let name = &pre_init.sig.ident;
+
+        stmts.push(quote!(#name(#name::Context::new(&mut core));));
+    }
+
+    // `#[lazy]` locals are written once, before any task can reach them
+    for (task_name, name, task_local) in app.declared_local_resources() {
+        if !util::is_lazy_local(extra, &task_name, name) {
+            continue;
+        }
+
+        let cfgs = &task_local.cfgs;
+        let expr = &task_local.expr;
+        let mangled_name = util::declared_static_local_resource_ident(name, &task_name);
+
+        stmts.push(quote!(
+            #(#cfgs)*
+            #mangled_name.get_mut_unchecked().as_mut_ptr().write(#expr);
+        ));
+    }
+
+    // Populate the FreeQueue, the tasks that use a pool share the one of the pool and unbounded
+    // tasks have none
     for (name, task) in &app.software_tasks {
-        let cap = task.args.capacity;
+        if extra.task_pools.contains_key(name) || extra.unbounded.contains(name) {
+            continue;
+        }
+
+        // Unsuffixed, the indices get the type of the free queue
+        let cap =
+            util::capacity_literal(util::task_capacity(extra, name, task.args.capacity).into());
         let fq_ident = util::fq_ident(name);
 
         stmts.push(quote!(
@@ -23,10 +58,14 @@ pub fn codegen(app: &App, analysis: &Analysis, extra: &Extra) -> Vec<TokenStream
         ));
     }
 
-    stmts.push(quote!(
-        // To set the variable in cortex_m so the peripherals cannot be taken multiple times
-        let mut core: rtic::export::Peripherals = rtic::export::Peripherals::steal().into();
-    ));
+    for (pool, cap) in &extra.pools {
+        let fq_ident = util::pool_fq_ident(pool);
+        let cap = util::capacity_literal((*cap).into());
+
+        stmts.push(quote!(
+            (0..#cap).for_each(|i| #fq_ident.get_mut_unchecked().enqueue_unchecked(i));
+        ));
+    }
 
     let device = &extra.device;
     let nvic_prio_bits = quote!(#device::NVIC_PRIO_BITS);
@@ -38,19 +77,40 @@ pub fn codegen(app: &App, analysis: &Analysis, extra: &Extra) -> Vec<TokenStream
         stmts.push(quote!(let _ = #rt_err::#interrupt::#name;));
     }
 
-    let interrupt_ids = analysis.interrupts.iter().map(|(p, (id, _))| (p, id));
+    // When there are zero latency tasks the highest priority is reserved for them
+    let max_priority = if extra.zero_latency.is_empty() {
+        quote!((1 << #nvic_prio_bits))
+    } else {
+        quote!(((1 << #nvic_prio_bits) - 1))
+    };
+
+    let interrupt_ids = analysis
+        .interrupts
+        .iter()
+        .map(|(p, (id, _))| (quote!(#p), false, id));
 
     // Unmask interrupts and set their priorities
-    for (&priority, name) in interrupt_ids.chain(app.hardware_tasks.values().flat_map(|task| {
-        if !util::is_exception(&task.args.binds) {
-            Some((&task.args.priority, &task.args.binds))
-        } else {
-            // We do exceptions in another pass
-            None
-        }
-    })) {
+    for (priority, zero_latency, name) in
+        interrupt_ids.chain(app.hardware_tasks.iter().flat_map(|(task_name, task)| {
+            if util::is_fixed_priority_exception(&task.args.binds) {
+                // `NonMaskableInt` and `HardFault` are always enabled and their priority is fixed
+                None
+            } else if !util::is_exception(&task.args.binds) {
+                Some((
+                    util::hardware_task_priority(task_name, task, extra),
+                    extra.zero_latency.contains(task_name),
+                    &task.args.binds,
+                ))
+            } else {
+                // We do exceptions in another pass
+                None
+            }
+        }))
+    {
         // Compile time assert that this priority is supported by the device
-        stmts.push(quote!(let _ = [(); ((1 << #nvic_prio_bits) - #priority as usize)];));
+        if !zero_latency {
+            stmts.push(quote!(let _ = [(); (#max_priority - #priority as usize)];));
+        }
 
         stmts.push(quote!(
             core.NVIC.set_priority(
@@ -65,15 +125,18 @@ pub fn codegen(app: &App, analysis: &Analysis, extra: &Extra) -> Vec<TokenStream
     }
 
     // Set exception priorities
-    for (name, priority) in app.hardware_tasks.values().filter_map(|task| {
-        if util::is_exception(&task.args.binds) {
-            Some((&task.args.binds, task.args.priority))
-        } else {
-            None
-        }
-    }) {
+    for (task_name, task) in app
+        .hardware_tasks
+        .iter()
+        .filter(|(_, task)| util::is_exception(&task.args.binds))
+    {
+        let name = &task.args.binds;
+        let priority = util::hardware_task_priority(task_name, task, extra);
+
         // Compile time assert that this priority is supported by the device
-        stmts.push(quote!(let _ = [(); ((1 << #nvic_prio_bits) - #priority as usize)];));
+        if !extra.zero_latency.contains(task_name) {
+            stmts.push(quote!(let _ = [(); (#max_priority - #priority as usize)];));
+        }
 
         stmts.push(quote!(core.SCB.set_priority(
             rtic::export::SystemHandler::#name,
@@ -86,12 +149,12 @@ pub fn codegen(app: &App, analysis: &Analysis, extra: &Extra) -> Vec<TokenStream
         let priority = if let Some(prio) = monotonic.args.priority {
             quote! { #prio }
         } else {
-            quote! { (1 << #nvic_prio_bits) }
+            quote! { #max_priority }
         };
         let binds = &monotonic.args.binds;
 
         // Compile time assert that this priority is supported by the device
-        stmts.push(quote!(let _ = [(); ((1 << #nvic_prio_bits) - #priority as usize)];));
+        stmts.push(quote!(let _ = [(); (#max_priority - #priority as usize)];));
 
         let mono_type = &monotonic.ty;
 
@@ -131,3 +194,57 @@ pub fn codegen(app: &App, analysis: &Analysis, extra: &Extra) -> Vec<TokenStream
 
     stmts
 }
+
+/// Generates support code for the `#[pre_init]` function
+pub fn hook(
+    extra: &Extra,
+) -> (
+    // root_pre_init -- the `${pre_init}` module, which contains `${pre_init}::Context`
+    Vec<TokenStream2>,
+    // user_pre_init -- the `#[pre_init]` function written by the user
+    Option<TokenStream2>,
+) {
+    let pre_init = if let Some(pre_init) = &extra.pre_init {
+        pre_init
+    } else {
+        return (vec![], None);
+    };
+
+    let name = &pre_init.sig.ident;
+    let attrs = &pre_init.attrs;
+    let inputs = &pre_init.sig.inputs;
+    let block = &pre_init.block;
+    let internal_context_name = util::internal_task_ident(name, "Context");
+
+    let root = vec![quote!(
+        /// Execution context
+        #[allow(non_snake_case)]
+        #[allow(non_camel_case_types)]
+        pub struct #internal_context_name<'a> {
+            /// Core (Cortex-M) peripherals
+            pub core: &'a mut rtic::export::Peripherals,
+        }
+
+        impl<'a> #internal_context_name<'a> {
+            #[inline(always)]
+            pub unsafe fn new(core: &'a mut rtic::export::Peripherals) -> Self {
+                #internal_context_name { core }
+            }
+        }
+
+        #[allow(non_snake_case)]
+        #[doc = "Function that runs before RTIC initializes its memory"]
+        pub mod #name {
+            pub use super::#internal_context_name as Context;
+        }
+    )];
+
+    let user = quote!(
+        #(#attrs)*
+        #[inline(always)]
+        #[allow(non_snake_case)]
+        fn #name(#inputs) #block
+    );
+
+    (root, Some(user))
+}
@@ -0,0 +1,74 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use rtic_syntax::ast::App;
+
+use crate::{check::Extra, codegen::util};
+
+/// Generates the `init::Retained` struct, with the values the retained resources kept across the
+/// last reset
+pub fn codegen(app: &App, extra: &Extra) -> Option<TokenStream2> {
+    let shared = app
+        .shared_resources
+        .iter()
+        .filter(|(name, _)| extra.shared_retained.contains(*name))
+        .map(|(name, res)| {
+            (
+                name,
+                &res.ty,
+                &res.cfgs,
+                util::static_shared_resource_ident(name),
+            )
+        });
+    let local = app
+        .local_resources
+        .iter()
+        .filter(|(name, _)| extra.local_retained.contains(*name))
+        .map(|(name, res)| {
+            (
+                name,
+                &res.ty,
+                &res.cfgs,
+                util::static_local_resource_ident(name),
+            )
+        });
+
+    let mut fields = vec![];
+    let mut values = vec![];
+    for (name, ty, cfgs, mangled_name) in shared.chain(local) {
+        let salt = util::retained_salt_ident(name);
+
+        fields.push(quote!(
+            #(#cfgs)*
+            pub #name: Option<#ty>
+        ));
+
+        // The check only matches if the value was sealed after its last write, by this firmware
+        values.push(quote!(
+            #(#cfgs)*
+            #name: #mangled_name.get_unchecked().read(#salt)
+        ));
+    }
+
+    if fields.is_empty() {
+        return None;
+    }
+
+    let ident = util::internal_task_ident(&app.init.name, "Retained");
+    Some(quote!(
+        /// The values the retained resources kept across the last reset, `None` after a power-on
+        /// reset or when the value doesn't pass its check
+        #[allow(non_camel_case_types)]
+        pub struct #ident {
+            #(#fields,)*
+        }
+
+        impl #ident {
+            #[inline(always)]
+            pub unsafe fn new() -> Self {
+                #ident {
+                    #(#values,)*
+                }
+            }
+        }
+    ))
+}
@@ -1,6 +1,6 @@
 use proc_macro2::TokenStream as TokenStream2;
 use quote::quote;
-use rtic_syntax::{analyze::Ownership, ast::App};
+use rtic_syntax::ast::App;
 
 use crate::{analyze::Analysis, check::Extra, codegen::util};
 
@@ -23,27 +23,41 @@ pub fn codegen(
         let ty = &res.ty;
         let mangled_name = &util::static_shared_resource_ident(&name);
 
-        // late resources in `util::link_section_uninit`
-        let section = util::link_section_uninit();
+        // late resources in `util::link_section_uninit`, the ones with a constant initializer
+        // in `.data` or `.bss`
+        let (section, value) = if let Some(init) = extra.shared_inits.get(name) {
+            (None, quote!(core::mem::MaybeUninit::new(#init)))
+        } else {
+            (
+                util::link_section_uninit(),
+                quote!(core::mem::MaybeUninit::uninit()),
+            )
+        };
         let attrs = &res.attrs;
 
         // For future use
         // let doc = format!(" RTIC internal: {}:{}", file!(), line!());
-        mod_app.push(quote!(
-            #[allow(non_camel_case_types)]
-            #[allow(non_upper_case_globals)]
-            // #[doc = #doc]
-            #[doc(hidden)]
-            #(#attrs)*
-            #(#cfgs)*
-            #section
-            static #mangled_name: rtic::RacyCell<core::mem::MaybeUninit<#ty>> = rtic::RacyCell::new(core::mem::MaybeUninit::uninit());
-        ));
+        // A retained resource keeps the check over its value next to it
+        if extra.shared_retained.contains(name) {
+            mod_app.push(util::retained_storage(name, &mangled_name, ty, cfgs, attrs));
+        } else {
+            mod_app.push(quote!(
+                #[allow(non_camel_case_types)]
+                #[allow(non_upper_case_globals)]
+                // #[doc = #doc]
+                #[doc(hidden)]
+                #(#attrs)*
+                #(#cfgs)*
+                #section
+                static #mangled_name: rtic::RacyCell<core::mem::MaybeUninit<#ty>> = rtic::RacyCell::new(#value);
+            ));
+        }
 
         // For future use
         // let doc = format!(" RTIC internal: {}:{}", file!(), line!());
 
-        if !res.properties.lock_free {
+        // Frozen resources are only read, they need no proxy
+        if !res.properties.lock_free && !extra.frozen.contains(name) {
             mod_resources.push(quote!(
                 // #[doc = #doc]
                 #[doc(hidden)]
@@ -72,11 +86,13 @@ pub fn codegen(
                 #mangled_name.get_mut_unchecked().as_mut_ptr()
             );
 
-            let ceiling = match analysis.ownerships.get(name) {
-                Some(Ownership::Owned { priority }) => *priority,
-                Some(Ownership::CoOwned { priority }) => *priority,
-                Some(Ownership::Contended { ceiling }) => *ceiling,
-                None => 0,
+            let ceiling = util::shared_resource_ceiling(analysis, name);
+
+            // The check of a retained resource is updated before the lock is released
+            let after = if extra.shared_retained.contains(name) {
+                Some(util::retained_seal(name, mangled_name, cfgs))
+            } else {
+                None
             };
 
             // For future use
@@ -90,6 +106,7 @@ pub fn codegen(
                 quote!(#ty),
                 ceiling,
                 ptr,
+                after,
             ));
         }
     }
@@ -2,10 +2,15 @@ use proc_macro2::TokenStream as TokenStream2;
 use quote::quote;
 use rtic_syntax::{ast::App, Context};
 
-use crate::codegen::util;
+use crate::{check::Extra, codegen::util};
 
 /// Generate shared resources structs
-pub fn codegen(ctxt: Context, needs_lt: &mut bool, app: &App) -> (TokenStream2, TokenStream2) {
+pub fn codegen(
+    ctxt: Context,
+    needs_lt: &mut bool,
+    app: &App,
+    extra: &Extra,
+) -> (TokenStream2, TokenStream2) {
     let mut lt = None;
 
     let resources = match ctxt {
@@ -34,7 +39,13 @@ pub fn codegen(ctxt: Context, needs_lt: &mut bool, app: &App) -> (TokenStream2,
         let ty = &res.ty;
         let mangled_name = util::static_shared_resource_ident(&name);
 
-        if !res.properties.lock_free {
+        if extra.frozen.contains(name) {
+            // Nothing writes to it after `#[init]`, so the reference can be `'static`
+            fields.push(quote!(
+                #(#cfgs)*
+                pub #name: &'static #ty
+            ));
+        } else if !res.properties.lock_free {
             if access.is_shared() {
                 lt = Some(quote!('a));
 
@@ -26,12 +26,67 @@ pub fn codegen(
     let mut mod_app = vec![];
     let mut root = vec![];
     let mut user_tasks = vec![];
+    let index_ty = util::index_ty(app, extra);
+
+    // Message pools: one slot fits the message of any of the tasks that share the pool
+    for (pool, cap) in &extra.pools {
+        let fields = app
+            .software_tasks
+            .iter()
+            .filter(|(name, _)| extra.task_pools.get(*name) == Some(pool))
+            .map(|(name, task)| {
+                let cfgs = &task.cfgs;
+                let (_, _, _, input_ty) = util::regroup_inputs(&task.inputs);
+
+                quote!(
+                    #(#cfgs)*
+                    #name: core::mem::ManuallyDrop<#input_ty>
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let slot_ty = util::pool_slot_ident(pool);
+        let pool_ident = util::pool_ident(pool);
+        let fq = util::pool_fq_ident(pool);
+        let cap_lit = util::capacity_literal(*cap as usize);
+        let cap_lit_p1 = util::capacity_literal(*cap as usize + 1);
+        let elems = (0..*cap)
+            .map(|_| quote!(core::mem::MaybeUninit::uninit()))
+            .collect::<Vec<_>>();
+        let section = util::link_section_uninit();
+
+        mod_app.push(quote!(
+            #[allow(non_camel_case_types)]
+            #[allow(non_snake_case)]
+            #[allow(dead_code)]
+            #[doc(hidden)]
+            #[repr(C)]
+            pub union #slot_ty {
+                #(#fields,)*
+            }
+
+            #[allow(non_camel_case_types)]
+            #[allow(non_upper_case_globals)]
+            #[doc(hidden)]
+            static #fq: rtic::RacyCell<rtic::export::SCFQ<#index_ty, #cap_lit_p1>> =
+                rtic::RacyCell::new(rtic::export::Queue::new());
+
+            #section
+            #[allow(non_camel_case_types)]
+            #[allow(non_upper_case_globals)]
+            #[doc(hidden)]
+            static #pool_ident: rtic::RacyCell<[core::mem::MaybeUninit<#slot_ty>; #cap_lit]> =
+                rtic::RacyCell::new([#(#elems,)*]);
+        ));
+    }
 
     for (name, task) in &app.software_tasks {
         let inputs = &task.inputs;
         let (_, _, _, input_ty) = util::regroup_inputs(inputs);
+        let pooled = extra.task_pools.contains_key(name);
+        let unbounded = extra.unbounded.contains(name);
 
-        let cap = task.args.capacity;
+        let cap = util::task_capacity(extra, name, task.args.capacity);
         let cap_lit = util::capacity_literal(cap as usize);
         let cap_lit_p1 = util::capacity_literal(cap as usize + 1);
 
@@ -40,25 +95,28 @@ pub fn codegen(
 
         let (fq_ty, fq_expr, mk_uninit): (_, _, Box<dyn Fn() -> Option<_>>) = {
             (
-                quote!(rtic::export::SCFQ<#cap_lit_p1>),
+                quote!(rtic::export::SCFQ<#index_ty, #cap_lit_p1>),
                 quote!(rtic::export::Queue::new()),
                 Box::new(|| util::link_section_uninit()),
             )
         };
-        mod_app.push(quote!(
-            // /// Queue version of a free-list that keeps track of empty slots in
-            // /// the following buffers
-            #[allow(non_camel_case_types)]
-            #[allow(non_upper_case_globals)]
-            #[doc(hidden)]
-            static #fq: rtic::RacyCell<#fq_ty> = rtic::RacyCell::new(#fq_expr);
-        ));
+        if !pooled && !unbounded {
+            mod_app.push(quote!(
+                // /// Queue version of a free-list that keeps track of empty slots in
+                // /// the following buffers
+                #[allow(non_camel_case_types)]
+                #[allow(non_upper_case_globals)]
+                #[doc(hidden)]
+                static #fq: rtic::RacyCell<#fq_ty> = rtic::RacyCell::new(#fq_expr);
+            ));
+        }
 
         let elems = &(0..cap)
             .map(|_| quote!(core::mem::MaybeUninit::uninit()))
             .collect::<Vec<_>>();
 
-        for (_, monotonic) in &app.monotonics {
+        // Unbounded tasks can't be scheduled
+        for (_, monotonic) in app.monotonics.iter().filter(|_| !unbounded) {
             let instants = util::monotonic_instants_ident(name, &monotonic.ident);
             let mono_type = &monotonic.ty;
 
@@ -78,17 +136,55 @@ pub fn codegen(
             ));
         }
 
-        let uninit = mk_uninit();
-        let inputs_ident = util::inputs_ident(name);
-        mod_app.push(quote!(
-            #uninit
-            // /// Buffer that holds the inputs of a task
-            #[allow(non_camel_case_types)]
-            #[allow(non_upper_case_globals)]
-            #[doc(hidden)]
-            static #inputs_ident: rtic::RacyCell<[core::mem::MaybeUninit<#input_ty>; #cap_lit]> =
-                rtic::RacyCell::new([#(#elems,)*]);
-        ));
+        if inputs.is_empty() {
+            let signaled = util::signaled_ident(name);
+            mod_app.push(quote!(
+                // /// Set while the task is in the ready queue because of `signal`
+                #[allow(non_camel_case_types)]
+                #[allow(non_upper_case_globals)]
+                #[doc(hidden)]
+                static #signaled: core::sync::atomic::AtomicBool =
+                    core::sync::atomic::AtomicBool::new(false);
+            ));
+        }
+
+        if let Some(reply) = extra.replies.get(name) {
+            let reply_to = util::reply_to_ident(name);
+            mod_app.push(quote!(
+                // /// Buffer that holds the task each reply is delivered to, `None` for `spawn`
+                #[allow(non_camel_case_types)]
+                #[allow(non_upper_case_globals)]
+                #[doc(hidden)]
+                static #reply_to:
+                    rtic::RacyCell<[Option<fn(#reply) -> Result<(), #reply>>; #cap_lit]> =
+                    rtic::RacyCell::new([None; #cap_lit]);
+            ));
+        }
+
+        // The inputs of the tasks that use a pool are in the slots of the pool, the ones of
+        // unbounded tasks are on the heap
+        if unbounded {
+            let queue = util::unbounded_queue_ident(name);
+            mod_app.push(quote!(
+                #[allow(non_camel_case_types)]
+                #[allow(non_upper_case_globals)]
+                #[doc(hidden)]
+                static #queue: rtic::RacyCell<rtic::export::UnboundedQueue<#input_ty>> =
+                    rtic::RacyCell::new(rtic::export::UnboundedQueue::new());
+            ));
+        } else if !pooled {
+            let uninit = mk_uninit();
+            let inputs_ident = util::inputs_ident(name);
+            mod_app.push(quote!(
+                #uninit
+                // /// Buffer that holds the inputs of a task
+                #[allow(non_camel_case_types)]
+                #[allow(non_upper_case_globals)]
+                #[doc(hidden)]
+                static #inputs_ident: rtic::RacyCell<[core::mem::MaybeUninit<#input_ty>; #cap_lit]> =
+                    rtic::RacyCell::new([#(#elems,)*]);
+            ));
+        }
 
         // `${task}Resources`
         let mut shared_needs_lt = false;
@@ -100,6 +196,7 @@ pub fn codegen(
                 Context::SoftwareTask(name),
                 &mut local_needs_lt,
                 app,
+                extra,
             );
 
             root.push(item);
@@ -112,6 +209,7 @@ pub fn codegen(
                 Context::SoftwareTask(name),
                 &mut shared_needs_lt,
                 app,
+                extra,
             );
 
             root.push(item);
@@ -124,11 +222,19 @@ pub fn codegen(
             let attrs = &task.attrs;
             let cfgs = &task.cfgs;
             let stmts = &task.stmts;
+            let output = extra.replies.get(name).map(|reply| quote!(-> #reply));
+            // `by_ref` tasks get a reference into the buffer instead of the message
+            let inputs = if let Some(ty) = extra.by_ref.get(name) {
+                let pat = &inputs[0].pat;
+                vec![quote!(#pat: #ty)]
+            } else {
+                inputs.iter().map(|input| quote!(#input)).collect()
+            };
             user_tasks.push(quote!(
                 #(#attrs)*
                 #(#cfgs)*
                 #[allow(non_snake_case)]
-                fn #name(#context: #name::Context #(,#inputs)*) {
+                fn #name(#context: #name::Context #(,#inputs)*) #output {
                     use rtic::Mutex as _;
                     use rtic::mutex_prelude::*;
 
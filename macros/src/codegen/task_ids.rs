@@ -0,0 +1,170 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use rtic_syntax::{ast::App, Context};
+use syn::Attribute;
+
+use crate::{analyze::Analysis, check::Extra, codegen::util};
+
+/// Generates the `Task` enum, which names every task of the application
+pub fn codegen(app: &App) -> Vec<TokenStream2> {
+    let mut items = vec![];
+
+    // `#[init]` and `#[idle]` can't be disabled with `#[cfg]`
+    let no_cfgs: &[Attribute] = &[];
+    let tasks = Some((&app.init.name, no_cfgs))
+        .into_iter()
+        .chain(app.idle.as_ref().map(|idle| (&idle.name, no_cfgs)))
+        .chain(
+            app.hardware_tasks
+                .iter()
+                .map(|(name, task)| (name, &task.cfgs[..])),
+        )
+        .chain(
+            app.software_tasks
+                .iter()
+                .map(|(name, task)| (name, &task.cfgs[..])),
+        )
+        .collect::<Vec<_>>();
+
+    let variants = tasks
+        .iter()
+        .map(|(name, cfgs)| {
+            quote!(
+                #(#cfgs)*
+                #name
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let all = tasks
+        .iter()
+        .map(|(name, cfgs)| {
+            quote!(
+                #(#cfgs)*
+                Task::#name
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let arms = tasks
+        .iter()
+        .map(|(name, cfgs)| {
+            quote!(
+                #(#cfgs)*
+                Task::#name => #name::METADATA,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    items.push(quote!(
+        /// Names the tasks of the application
+        #[allow(non_camel_case_types)]
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum Task {
+            #(#variants,)*
+        }
+
+        impl Task {
+            /// Every task of the application: `#[init]`, `#[idle]`, the hardware tasks and then
+            /// the software tasks
+            pub const ALL: &'static [Task] = &[#(#all,)*];
+
+            /// Compile time description of the task
+            pub const fn metadata(self) -> rtic::TaskMetadata {
+                match self {
+                    #(#arms)*
+                }
+            }
+
+            /// The name of the task, as written in the application
+            pub const fn name(self) -> &'static str {
+                self.metadata().name
+            }
+        }
+    ));
+
+    items
+}
+
+/// Generates the `METADATA` constant of the module of a task
+pub fn metadata(ctxt: Context, app: &App, analysis: &Analysis, extra: &Extra) -> TokenStream2 {
+    let task_name = ctxt.ident(app).to_string();
+    let priority = util::task_priority(ctxt, app, extra);
+
+    let (kind, binds, capacity, resources) = match ctxt {
+        Context::Init => (quote!(Init), None, None, None),
+        Context::Idle => (
+            quote!(Idle),
+            None,
+            None,
+            Some(&app.idle.as_ref().unwrap().args.shared_resources),
+        ),
+        Context::HardwareTask(name) => {
+            let task = &app.hardware_tasks[name];
+            let binds = task.args.binds.to_string();
+
+            (
+                quote!(Hardware),
+                Some(binds),
+                None,
+                Some(&task.args.shared_resources),
+            )
+        }
+        Context::SoftwareTask(name) => {
+            let task = &app.software_tasks[name];
+            let capacity = if extra.unbounded.contains(name) {
+                None
+            } else {
+                Some(util::task_capacity(extra, name, task.args.capacity))
+            };
+
+            (
+                quote!(Software),
+                None,
+                capacity,
+                Some(&task.args.shared_resources),
+            )
+        }
+    };
+
+    let binds = if let Some(binds) = binds {
+        quote!(Some(#binds))
+    } else {
+        quote!(None)
+    };
+
+    let capacity = if let Some(capacity) = capacity {
+        quote!(Some(#capacity))
+    } else {
+        quote!(None)
+    };
+
+    let resources = resources
+        .into_iter()
+        .flatten()
+        .map(|(name, access)| {
+            let cfgs = &app.shared_resources[name].cfgs;
+            let by_ref = access.is_shared();
+            let ceiling = util::shared_resource_ceiling(analysis, name);
+            let name = name.to_string();
+
+            quote!(
+                #(#cfgs)*
+                rtic::ResourceAccess {
+                    name: #name,
+                    by_ref: #by_ref,
+                    ceiling: #ceiling,
+                }
+            )
+        })
+        .collect::<Vec<_>>();
+
+    quote!(rtic::TaskMetadata {
+        name: #task_name,
+        kind: rtic::TaskKind::#kind,
+        priority: #priority,
+        binds: #binds,
+        capacity: #capacity,
+        shared_resources: &[#(#resources,)*],
+    })
+}
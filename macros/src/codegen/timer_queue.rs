@@ -5,8 +5,9 @@ use rtic_syntax::ast::App;
 use crate::{analyze::Analysis, check::Extra, codegen::util};
 
 /// Generates timer queues and timer queue handlers
-pub fn codegen(app: &App, analysis: &Analysis, _extra: &Extra) -> Vec<TokenStream2> {
+pub fn codegen(app: &App, analysis: &Analysis, extra: &Extra) -> Vec<TokenStream2> {
     let mut items = vec![];
+    let interrupt_free = &util::interrupt_free(extra);
 
     if !app.monotonics.is_empty() {
         // Generate the marker counter used to track for `cancel` and `reschedule`
@@ -21,20 +22,37 @@ pub fn codegen(app: &App, analysis: &Analysis, _extra: &Extra) -> Vec<TokenStrea
 
         let t = util::schedule_t_ident();
 
-        // Enumeration of `schedule`-able tasks
+        // Enumeration of `schedule`-able tasks, of the rate limited tasks to unmask, of the
+        // budgets that can expire and of the watchdog windows that can close
         {
-            let variants = app
-                .software_tasks
-                .iter()
-                .map(|(name, task)| {
-                    let cfgs = &task.cfgs;
-
-                    quote!(
-                        #(#cfgs)*
-                        #name
+            let variants =
+                app.software_tasks
+                    .iter()
+                    .map(|(name, task)| (name.clone(), &task.cfgs[..]))
+                    .chain(
+                        extra
+                            .rate_limits
+                            .keys()
+                            .map(|name| (name.clone(), &app.hardware_tasks[name].cfgs[..])),
                     )
-                })
-                .collect::<Vec<_>>();
+                    .chain(
+                        extra.budgets.keys().map(|name| {
+                            (util::budget_variant_ident(name), util::task_cfgs(app, name))
+                        }),
+                    )
+                    .chain(extra.watchdogs.keys().map(|name| {
+                        (
+                            util::watchdog_variant_ident(name),
+                            util::task_cfgs(app, name),
+                        )
+                    }))
+                    .map(|(name, cfgs)| {
+                        quote!(
+                            #(#cfgs)*
+                            #name
+                        )
+                    })
+                    .collect::<Vec<_>>();
 
             // For future use
             // let doc = "Tasks that can be scheduled".to_string();
@@ -50,6 +68,37 @@ pub fn codegen(app: &App, analysis: &Analysis, _extra: &Extra) -> Vec<TokenStrea
         }
     }
 
+    // Completion flags of the tasks supervised by the watchdog
+    if !extra.watchdogs.is_empty() {
+        let unhealthy = util::watchdog_unhealthy_ident();
+        items.push(quote!(
+            #[doc(hidden)]
+            #[allow(non_upper_case_globals)]
+            static #unhealthy: core::sync::atomic::AtomicUsize =
+                core::sync::atomic::AtomicUsize::new(0);
+        ));
+
+        for name in extra.watchdogs.keys() {
+            let cfgs = util::task_cfgs(app, name);
+            let completed = util::watchdog_completed_ident(name);
+            let healthy = util::watchdog_healthy_ident(name);
+
+            items.push(quote!(
+                #(#cfgs)*
+                #[doc(hidden)]
+                #[allow(non_upper_case_globals)]
+                static #completed: core::sync::atomic::AtomicBool =
+                    core::sync::atomic::AtomicBool::new(false);
+
+                #(#cfgs)*
+                #[doc(hidden)]
+                #[allow(non_upper_case_globals)]
+                static #healthy: core::sync::atomic::AtomicBool =
+                    core::sync::atomic::AtomicBool::new(true);
+            ));
+        }
+    }
+
     for (_, monotonic) in &app.monotonics {
         let monotonic_name = monotonic.ident.to_string();
         let tq = util::tq_ident(&monotonic_name);
@@ -61,13 +110,36 @@ pub fn codegen(app: &App, analysis: &Analysis, _extra: &Extra) -> Vec<TokenStrea
         {
             // For future use
             // let doc = &format!("Timer queue for {}", monotonic_name);
-            let cap: usize = app
-                .software_tasks
-                .iter()
-                .map(|(_name, task)| task.args.capacity as usize)
-                .sum();
+            // Each rate limited task has at most one entry, made when it gets masked
+            let rate_limited = extra
+                .rate_limits
+                .values()
+                .filter(|(_, m)| *m == monotonic.ident)
+                .count();
+            // A task can't preempt itself so it has at most one budget entry
+            let budgets = extra
+                .budgets
+                .values()
+                .filter(|(_, m)| *m == monotonic.ident)
+                .count();
+            // The window of a supervised task is always open, so it has exactly one entry
+            let watchdogs = extra
+                .watchdogs
+                .values()
+                .filter(|(_, m)| *m == monotonic.ident)
+                .count();
+            let cap: usize = util::pending_capacity(
+                extra,
+                app.software_tasks
+                    .iter()
+                    .filter(|(name, _)| !extra.unbounded.contains(*name))
+                    .map(|(name, task)| (name, task.args.capacity)),
+            ) + rate_limited
+                + budgets
+                + watchdogs;
             let n = util::capacity_literal(cap);
-            let tq_ty = quote!(rtic::export::TimerQueue<#mono_type, #t, #n>);
+            let index_ty = util::index_ty(app, extra);
+            let tq_ty = quote!(rtic::export::TimerQueue<#mono_type, #t, #index_ty, #n>);
 
             // For future use
             // let doc = format!(" RTIC internal: {}:{}", file!(), line!());
@@ -96,17 +168,20 @@ pub fn codegen(app: &App, analysis: &Analysis, _extra: &Extra) -> Vec<TokenStrea
             let enum_ = util::interrupt_ident();
             let rt_err = util::rt_err_ident();
 
-            let arms = app
+            let mut arms = app
                 .software_tasks
                 .iter()
                 .map(|(name, task)| {
                     let cfgs = &task.cfgs;
                     let priority = task.args.priority;
-                    let rq = util::rq_ident(priority);
-                    let rqt = util::spawn_t_ident(priority);
+                    let rq_enqueue = util::rq_enqueue(app, extra, name, quote!(index));
 
                     // The interrupt that runs the task dispatcher
-                    let interrupt = &analysis.interrupts.get(&priority).expect("RTIC-ICE: interrupt not found").0;
+                    let interrupt = &analysis
+                        .interrupts
+                        .get(&priority)
+                        .expect("RTIC-ICE: interrupt not found")
+                        .0;
 
                     let pend = {
                         quote!(
@@ -117,7 +192,7 @@ pub fn codegen(app: &App, analysis: &Analysis, _extra: &Extra) -> Vec<TokenStrea
                     quote!(
                         #(#cfgs)*
                         #t::#name => {
-                            rtic::export::interrupt::free(|_| #rq.get_mut_unchecked().split().0.enqueue_unchecked((#rqt::#name, index)));
+                            #interrupt_free(|_| { #rq_enqueue });
 
                             #pend
                         }
@@ -125,6 +200,122 @@ pub fn codegen(app: &App, analysis: &Analysis, _extra: &Extra) -> Vec<TokenStrea
                 })
                 .collect::<Vec<_>>();
 
+            // The window of a rate limited task is over: count whether it fired meanwhile, the
+            // NVIC keeps a single pending activation, and unmask it
+            for (name, (_, m)) in &extra.rate_limits {
+                if *m != monotonic.ident {
+                    continue;
+                }
+
+                let task = &app.hardware_tasks[name];
+                let cfgs = &task.cfgs;
+                let binds = &task.args.binds;
+                let suppressed = util::suppressed_ident(name);
+                let window = util::rate_limit_window_ident(name);
+
+                arms.push(quote!(
+                    #(#cfgs)*
+                    #t::#name => {
+                        if rtic::export::NVIC::is_pending(#rt_err::#enum_::#binds) {
+                            #suppressed.store(
+                                #suppressed.load(core::sync::atomic::Ordering::Relaxed).wrapping_add(1),
+                                core::sync::atomic::Ordering::Relaxed,
+                            );
+                        }
+
+                        // `enable` doesn't unmask the task if it may not run in the current mode
+                        #window.store(false, core::sync::atomic::Ordering::Relaxed);
+                        #name::enable();
+                    }
+                ));
+            }
+
+            // The task was still running when its budget expired
+            for (name, (_, m)) in &extra.budgets {
+                if *m != monotonic.ident {
+                    continue;
+                }
+
+                let cfgs = util::task_cfgs(app, name);
+                let variant = util::budget_variant_ident(name);
+                let budget_overrun = &extra
+                    .budget_overrun
+                    .as_ref()
+                    .expect("UNREACHABLE")
+                    .sig
+                    .ident;
+
+                arms.push(quote!(
+                    #(#cfgs)*
+                    #t::#variant => #budget_overrun(Task::#name),
+                ));
+            }
+
+            // The window of a supervised task closed: check that the task completed in it, then
+            // open the next one
+            for (name, (window, m)) in &extra.watchdogs {
+                if *m != monotonic.ident {
+                    continue;
+                }
+
+                let cfgs = util::task_cfgs(app, name);
+                let variant = util::watchdog_variant_ident(name);
+                let completed = util::watchdog_completed_ident(name);
+                let healthy = util::watchdog_healthy_ident(name);
+                let unhealthy = util::watchdog_unhealthy_ident();
+                let on_watchdog_timeout = &extra
+                    .on_watchdog_timeout
+                    .as_ref()
+                    .expect("UNREACHABLE")
+                    .sig
+                    .ident;
+                let enqueue = util::tq_enqueue(
+                    app,
+                    extra,
+                    m,
+                    quote!(#t::#variant),
+                    quote!(monotonics::#m::now() + #window),
+                );
+                let kick = extra.watchdog_kick.as_ref().map(|kick| {
+                    let kick = &kick.sig.ident;
+                    quote!(
+                        if #unhealthy.load(core::sync::atomic::Ordering::Relaxed) == 0 {
+                            #kick();
+                        }
+                    )
+                });
+
+                arms.push(quote!(
+                    #(#cfgs)*
+                    #t::#variant => {
+                        let completed = #interrupt_free(|_| {
+                            let completed = #completed.load(core::sync::atomic::Ordering::Relaxed);
+                            #completed.store(false, core::sync::atomic::Ordering::Relaxed);
+
+                            if completed != #healthy.load(core::sync::atomic::Ordering::Relaxed) {
+                                #healthy.store(completed, core::sync::atomic::Ordering::Relaxed);
+
+                                let unhealthy = #unhealthy.load(core::sync::atomic::Ordering::Relaxed);
+                                #unhealthy.store(
+                                    if completed { unhealthy - 1 } else { unhealthy + 1 },
+                                    core::sync::atomic::Ordering::Relaxed,
+                                );
+                            }
+
+                            completed
+                        });
+
+                        if !completed {
+                            #on_watchdog_timeout(Task::#name);
+                        }
+
+                        #enqueue;
+
+                        #kick
+                    }
+                ));
+            }
+
             let bound_interrupt = &monotonic.args.binds;
             let disable_isr = if &*bound_interrupt.to_string() == "SysTick" {
                 quote!(core::mem::transmute::<_, rtic::export::SYST>(()).disable_interrupt())
@@ -136,7 +327,7 @@ pub fn codegen(app: &App, analysis: &Analysis, _extra: &Extra) -> Vec<TokenStrea
                 #[no_mangle]
                 #[allow(non_snake_case)]
                 unsafe fn #bound_interrupt() {
-                    while let Some((task, index)) = rtic::export::interrupt::free(|_|
+                    while let Some((task, index)) = #interrupt_free(|_|
                         if let Some(mono) = #m_ident.get_mut_unchecked().as_mut() {
                             #tq.get_mut_unchecked().dequeue(|| #disable_isr, mono)
                         } else {
@@ -150,7 +341,7 @@ pub fn codegen(app: &App, analysis: &Analysis, _extra: &Extra) -> Vec<TokenStrea
                         }
                     }
 
-                    rtic::export::interrupt::free(|_| if let Some(mono) = #m_ident.get_mut_unchecked().as_mut() {
+                    #interrupt_free(|_| if let Some(mono) = #m_ident.get_mut_unchecked().as_mut() {
                         mono.on_interrupt();
                     });
                 }
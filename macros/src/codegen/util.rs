@@ -1,11 +1,16 @@
 use core::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::BTreeSet;
 
 use proc_macro2::{Span, TokenStream as TokenStream2};
 use quote::quote;
-use rtic_syntax::{ast::App, Context};
-use syn::{Attribute, Ident, LitInt, PatType};
+use rtic_syntax::{
+    analyze::Ownership,
+    ast::{App, HardwareTask, Monotonic, TaskLocal},
+    Context,
+};
+use syn::{Attribute, Ident, LitInt, PatType, Type};
 
-use crate::check::Extra;
+use crate::{analyze::Analysis, check::Extra};
 
 const RTIC_INTERNAL: &str = "__rtic_internal";
 
@@ -28,6 +33,7 @@ pub fn impl_mutex(
     ty: TokenStream2,
     ceiling: u8,
     ptr: TokenStream2,
+    after: Option<TokenStream2>,
 ) -> TokenStream2 {
     let (path, priority) = if resources_prefix {
         (quote!(shared_resources::#name), quote!(self.priority()))
@@ -35,6 +41,17 @@ pub fn impl_mutex(
         (quote!(#name), quote!(self.priority))
     };
 
+    // Runs at the ceiling once `f` is done with the resource
+    let f = if let Some(after) = after {
+        quote!(|r| {
+            let r = f(r);
+            #after
+            r
+        })
+    } else {
+        quote!(f)
+    };
+
     let device = &extra.device;
     quote!(
         #(#cfgs)*
@@ -52,7 +69,7 @@ pub fn impl_mutex(
                         #priority,
                         CEILING,
                         #device::NVIC_PRIO_BITS,
-                        f,
+                        #f,
                     )
                 }
             }
@@ -60,11 +77,125 @@ pub fn impl_mutex(
     )
 }
 
+/// Generates an identifier for the pending flag of the `signal` API
+pub fn signaled_ident(task: &Ident) -> Ident {
+    mark_internal_name(&format!("{}_SIGNALED", task))
+}
+
+/// Generates an identifier for the count of activations of a rate limited task that were held back
+pub fn suppressed_ident(task: &Ident) -> Ident {
+    mark_internal_name(&format!("{}_SUPPRESSED", task))
+}
+
+/// Generates an identifier for the flag of a rate limited task that is set while it's masked to
+/// respect its `min_interarrival`
+pub fn rate_limit_window_ident(task: &Ident) -> Ident {
+    mark_internal_name(&format!("{}_WINDOW", task))
+}
+
+/// Generates an identifier for the buffer of reply functions (`call` API)
+pub fn reply_to_ident(task: &Ident) -> Ident {
+    mark_internal_name(&format!("{}_REPLY_TO", task))
+}
+
 /// Generates an identifier for the `INPUTS` buffer (`spawn` & `schedule` API)
 pub fn inputs_ident(task: &Ident) -> Ident {
     mark_internal_name(&format!("{}_INPUTS", task))
 }
 
+/// Identifier of the heap allocated message queue of a `capacity = unbounded` task
+pub fn unbounded_queue_ident(task: &Ident) -> Ident {
+    mark_internal_name(&format!("{}_QUEUE", task))
+}
+
+/// Identifier of the slots of a message pool
+pub fn pool_ident(pool: &Ident) -> Ident {
+    mark_internal_name(&format!("pool_{}", pool))
+}
+
+/// Identifier of the union that fits the message of every task of a pool
+pub fn pool_slot_ident(pool: &Ident) -> Ident {
+    mark_internal_name(&format!("pool_{}_Slot", pool))
+}
+
+/// Identifier of the free queue of a message pool
+pub fn pool_fq_ident(pool: &Ident) -> Ident {
+    mark_internal_name(&format!("pool_{}_FQ", pool))
+}
+
+/// The free queue software task `task` takes its message slots from, the one of its pool if it
+/// has one
+pub fn task_fq_ident(extra: &Extra, task: &Ident) -> Ident {
+    if let Some(pool) = extra.task_pools.get(task) {
+        pool_fq_ident(pool)
+    } else {
+        fq_ident(task)
+    }
+}
+
+/// A `*mut #ty` to the message slot `index` of software task `task`
+pub fn input_slot(
+    extra: &Extra,
+    task: &Ident,
+    ty: &TokenStream2,
+    index: TokenStream2,
+) -> TokenStream2 {
+    // Every field of the `repr(C)` union of the pool is at offset 0
+    if let Some(pool) = extra.task_pools.get(task) {
+        let pool = pool_ident(pool);
+
+        quote!(
+            (#pool.get_mut_unchecked().get_unchecked_mut(usize::from(#index)).as_mut_ptr()
+                as *mut #ty)
+        )
+    } else {
+        let inputs = inputs_ident(task);
+
+        quote!(#inputs.get_mut_unchecked().get_unchecked_mut(usize::from(#index)).as_mut_ptr())
+    }
+}
+
+/// How many messages of software task `task` can be pending, the capacity of its pool if it has
+/// one
+pub fn task_capacity(extra: &Extra, task: &Ident, capacity: u8) -> u16 {
+    if let Some(pool) = extra.task_pools.get(task) {
+        extra.pools[pool]
+    } else if let Some(capacity) = extra.capacities.get(task) {
+        *capacity
+    } else {
+        u16::from(capacity)
+    }
+}
+
+/// The type of the slot indices, `u8` unless a task or pool has more than 255 slots
+///
+/// `MAX` is never a slot, `signal` uses it to mark its ready queue entries
+pub fn index_ty(app: &App, extra: &Extra) -> TokenStream2 {
+    if app
+        .software_tasks
+        .iter()
+        .any(|(name, task)| task_capacity(extra, name, task.args.capacity) > 255)
+    {
+        quote!(u16)
+    } else {
+        quote!(u8)
+    }
+}
+
+/// How many messages of `tasks` can be pending at once, a pool counts once however many of its
+/// tasks are in `tasks`
+pub fn pending_capacity<'a>(extra: &Extra, tasks: impl Iterator<Item = (&'a Ident, u8)>) -> usize {
+    let mut pools = BTreeSet::new();
+
+    tasks
+        .map(|(task, capacity)| match extra.task_pools.get(task) {
+            Some(pool) if pools.insert(pool) => usize::from(extra.pools[pool]),
+            Some(_) => 0,
+            None => usize::from(task_capacity(extra, task, capacity)),
+        })
+        .sum()
+}
+
 /// Generates an identifier for the `INSTANTS` buffer (`schedule` API)
 pub fn monotonic_instants_ident(task: &Ident, monotonic: &Ident) -> Ident {
     mark_internal_name(&format!("{}_{}_INSTANTS", task, monotonic))
@@ -96,6 +227,144 @@ pub fn is_exception(name: &Ident) -> bool {
     )
 }
 
+/// Whether `name` is an exception with a fixed priority that RTIC can't mask
+pub fn is_fixed_priority_exception(name: &Ident) -> bool {
+    let s = name.to_string();
+
+    matches!(&*s, "NonMaskableInt" | "HardFault")
+}
+
+/// Whether the app has tasks bound to exceptions with a fixed priority
+pub fn has_fixed_priority_tasks(app: &App) -> bool {
+    app.hardware_tasks
+        .values()
+        .any(|task| is_fixed_priority_exception(&task.args.binds))
+}
+
+/// The critical section that protects the queues shared between tasks
+///
+/// `interrupt::free` would also mask the zero latency tasks, so when there are any, a critical
+/// section that raises BASEPRI to the priority right below theirs is used instead
+pub fn interrupt_free(extra: &Extra) -> TokenStream2 {
+    if extra.zero_latency.is_empty() {
+        quote!(rtic::export::interrupt::free)
+    } else {
+        let device = &extra.device;
+        quote!(rtic::export::free_below_zero_latency::<_, _, { #device::NVIC_PRIO_BITS }>)
+    }
+}
+
+/// Makes the APIs that use the queues (`spawn`, `cancel`, `set_mode`, ..) return `ret` when called
+/// from a task RTIC can't mask, the critical sections that protect the queues don't apply there
+pub fn unmaskable_guard(app: &App, extra: &Extra, ret: TokenStream2) -> TokenStream2 {
+    let mut conditions = vec![];
+
+    if has_fixed_priority_tasks(app) {
+        conditions.push(quote!(rtic::export::in_fixed_priority_exception()));
+    }
+
+    if !extra.zero_latency.is_empty() {
+        let active = zero_latency_active_ident();
+        conditions.push(quote!(#active.load(core::sync::atomic::Ordering::Relaxed)));
+    }
+
+    if conditions.is_empty() {
+        quote!()
+    } else {
+        quote!(
+            if #(#conditions)||* {
+                return #ret;
+            }
+        )
+    }
+}
+
+/// Whether the handler `binds` can be masked, see the `disable` function of hardware tasks
+pub fn is_maskable(binds: &Ident) -> bool {
+    match &*binds.to_string() {
+        "SysTick" | "MemoryManagement" | "BusFault" | "UsageFault" | "SecureFault" => true,
+        _ => !is_exception(binds) && !is_fixed_priority_exception(binds),
+    }
+}
+
+/// Whether `task` may run in `mode`, an expression of type `Mode`
+///
+/// `None` for tasks that run in every mode
+pub fn mode_allowed(extra: &Extra, task: &Ident, mode: TokenStream2) -> Option<TokenStream2> {
+    extra
+        .task_modes
+        .get(task)
+        .map(|modes| quote!(matches!(#mode, #(Mode::#modes)|*)))
+}
+
+/// Generates the statement that rejects the spawn of a task that may not run in the current mode
+pub fn mode_spawn_guard(extra: &Extra, task: &Ident) -> TokenStream2 {
+    if let Some(allowed) = mode_allowed(extra, task, quote!(mode())) {
+        quote!(
+            if !#allowed {
+                return Err(input);
+            }
+        )
+    } else {
+        quote!()
+    }
+}
+
+/// Identifier of the current operating mode
+pub fn mode_ident() -> Ident {
+    mark_internal_name("MODE")
+}
+
+/// The logical priority of a hardware task
+///
+/// Zero latency tasks run at the highest priority the device supports, which no other task uses
+pub fn hardware_task_priority(name: &Ident, task: &HardwareTask, extra: &Extra) -> TokenStream2 {
+    if extra.zero_latency.contains(name) {
+        let device = &extra.device;
+        quote!((1 << #device::NVIC_PRIO_BITS))
+    } else {
+        let priority = task.args.priority;
+        quote!(#priority)
+    }
+}
+
+/// The priority the task of context `ctxt` runs at
+///
+/// `#[init]` and `#[idle]` run at priority 0 and tasks bound to `NonMaskableInt` and `HardFault`
+/// above every other task
+pub fn task_priority(ctxt: Context, app: &App, extra: &Extra) -> TokenStream2 {
+    match ctxt {
+        Context::Init | Context::Idle => quote!(0),
+        Context::HardwareTask(name) => {
+            let task = &app.hardware_tasks[name];
+            if is_fixed_priority_exception(&task.args.binds) {
+                quote!(u8::max_value())
+            } else {
+                hardware_task_priority(name, task, extra)
+            }
+        }
+        Context::SoftwareTask(name) => {
+            let priority = app.software_tasks[name].args.priority;
+            quote!(#priority)
+        }
+    }
+}
+
+/// The priority ceiling of shared resource `name`
+pub fn shared_resource_ceiling(analysis: &Analysis, name: &Ident) -> u8 {
+    match analysis.ownerships.get(name) {
+        Some(Ownership::Owned { priority }) => *priority,
+        Some(Ownership::CoOwned { priority }) => *priority,
+        Some(Ownership::Contended { ceiling }) => *ceiling,
+        None => 0,
+    }
+}
+
+/// Identifier of the flag that is set while a zero latency task runs
+pub fn zero_latency_active_ident() -> Ident {
+    mark_internal_name("ZERO_LATENCY_ACTIVE")
+}
+
 /// Mark a name as internal
 pub fn mark_internal_name(name: &str) -> Ident {
     Ident::new(&format!("{}_{}", RTIC_INTERNAL, name), Span::call_site())
@@ -218,9 +487,179 @@ pub fn local_resources_ident(ctxt: Context, app: &App) -> Ident {
 /// Generates an identifier for a ready queue
 ///
 /// There may be several task dispatchers, one for each priority level.
-/// The ready queues are SPSC queues
-pub fn rq_ident(priority: u8) -> Ident {
-    mark_internal_name(&format!("P{}_RQ", priority))
+/// The ready queues are SPSC queues, a level has one for each `order` of its tasks
+pub fn rq_ident(priority: u8, order: Option<u8>) -> Ident {
+    if let Some(order) = order {
+        mark_internal_name(&format!("P{}_O{}_RQ", priority, order))
+    } else {
+        mark_internal_name(&format!("P{}_RQ", priority))
+    }
+}
+
+/// Generates the statement that moves the task at `index` of its buffers into its ready queue
+pub fn rq_enqueue(app: &App, extra: &Extra, task: &Ident, index: TokenStream2) -> TokenStream2 {
+    let priority = app.software_tasks[task].args.priority;
+    let rq = rq_ident(priority, extra.orders.get(task).copied());
+    let t = spawn_t_ident(priority);
+
+    if extra.edf_levels.contains(&priority) {
+        // `check` makes sure there's a default monotonic
+        let m = &default_monotonic(app).expect("UNREACHABLE").ident;
+        let m_ident = monotonic_ident(&m.to_string());
+        let deadline = &extra.deadlines[task];
+
+        quote!({
+            let now = if #m_ident.get_mut_unchecked().is_none() {
+                rtic::time::Instant::new(0)
+            } else {
+                monotonics::#m::now()
+            };
+
+            #rq.get_mut_unchecked().enqueue_unchecked((#t::#task, #index), now + #deadline);
+        })
+    } else {
+        quote!(#rq.get_mut_unchecked().enqueue_unchecked((#t::#task, #index));)
+    }
+}
+
+/// Generates the expressions that unmask and pend the interrupt of `monotonic`
+pub fn monotonic_interrupt(monotonic: &Monotonic) -> (TokenStream2, TokenStream2) {
+    let m_isr = &monotonic.args.binds;
+
+    if &*m_isr.to_string() == "SysTick" {
+        (
+            quote!(core::mem::transmute::<_, rtic::export::SYST>(()).enable_interrupt()),
+            quote!(rtic::export::SCB::set_pendst()),
+        )
+    } else {
+        let rt_err = rt_err_ident();
+        let enum_ = interrupt_ident();
+        (
+            quote!(rtic::export::NVIC::unmask(#rt_err::#enum_::#m_isr)),
+            quote!(rtic::pend(#rt_err::#enum_::#m_isr)),
+        )
+    }
+}
+
+/// Generates an expression that adds an entry for `task`, a variant of the `SCHED_T` enum, to the
+/// timer queue of monotonic `m`; its value is the marker of the entry
+pub fn tq_enqueue(
+    app: &App,
+    extra: &Extra,
+    m: &Ident,
+    task: TokenStream2,
+    instant: TokenStream2,
+) -> TokenStream2 {
+    let (enable_interrupt, pend) = monotonic_interrupt(&app.monotonics[m]);
+    let tq = tq_ident(&m.to_string());
+    let m_ident = monotonic_ident(&m.to_string());
+    let tq_marker = timer_queue_marker_ident();
+    let interrupt_free = interrupt_free(extra);
+
+    quote!(#interrupt_free(|_| {
+        let marker = *#tq_marker.get_mut_unchecked();
+        *#tq_marker.get_mut_unchecked() = marker.wrapping_add(1);
+
+        let nr = rtic::export::NotReady {
+            instant: #instant,
+            index: 0,
+            task: #task,
+            marker,
+        };
+
+        #tq.get_mut_unchecked().enqueue_unchecked(
+            nr,
+            || #enable_interrupt,
+            || #pend,
+            #m_ident.get_mut_unchecked().as_mut());
+
+        marker
+    }))
+}
+
+/// Generates the statements that start and stop the budget of `task`, if it has one
+///
+/// Starting adds an entry to the timer queue that calls the `#[budget_overrun]` function, stopping
+/// cancels it
+pub fn budget_guard(
+    app: &App,
+    extra: &Extra,
+    task: &Ident,
+) -> (Option<TokenStream2>, Option<TokenStream2>) {
+    let (budget, m) = if let Some(budget) = extra.budgets.get(task) {
+        budget
+    } else {
+        return (None, None);
+    };
+
+    let t = schedule_t_ident();
+    let variant = budget_variant_ident(task);
+    let tq = tq_ident(&m.to_string());
+    let interrupt_free = interrupt_free(extra);
+    let enqueue = tq_enqueue(
+        app,
+        extra,
+        m,
+        quote!(#t::#variant),
+        quote!(monotonics::#m::now() + #budget),
+    );
+
+    (
+        Some(quote!(let budget_marker = #enqueue;)),
+        Some(quote!(#interrupt_free(|_| #tq.get_mut_unchecked().cancel_marker(budget_marker));)),
+    )
+}
+
+/// Variant of the `SCHED_T` enum for the budget of `task`
+pub fn budget_variant_ident(task: &Ident) -> Ident {
+    mark_internal_name(&format!("{}_budget", task))
+}
+
+/// Variant of the `SCHED_T` enum for the watchdog window of `task`
+pub fn watchdog_variant_ident(task: &Ident) -> Ident {
+    mark_internal_name(&format!("{}_watchdog", task))
+}
+
+/// Identifier of the flag a supervised task sets each time it completes
+pub fn watchdog_completed_ident(task: &Ident) -> Ident {
+    mark_internal_name(&format!("{}_COMPLETED", task))
+}
+
+/// Identifier of the flag that tells whether a supervised task completed in its last window
+pub fn watchdog_healthy_ident(task: &Ident) -> Ident {
+    mark_internal_name(&format!("{}_HEALTHY", task))
+}
+
+/// Identifier of the number of supervised tasks that didn't complete in their last window
+pub fn watchdog_unhealthy_ident() -> Ident {
+    mark_internal_name("WATCHDOG_UNHEALTHY")
+}
+
+/// Generates the statement that reports to the watchdog that `task` completed, if it's supervised
+pub fn watchdog_completed(extra: &Extra, task: &Ident) -> Option<TokenStream2> {
+    if extra.watchdogs.contains_key(task) {
+        let completed = watchdog_completed_ident(task);
+
+        Some(quote!(#completed.store(true, core::sync::atomic::Ordering::Relaxed);))
+    } else {
+        None
+    }
+}
+
+/// The `#[cfg]` attributes of a hardware or software task
+pub fn task_cfgs<'a>(app: &'a App, task: &Ident) -> &'a [Attribute] {
+    if let Some(task) = app.software_tasks.get(task) {
+        &task.cfgs
+    } else {
+        &app.hardware_tasks[task].cfgs
+    }
+}
+
+/// The monotonic declared with `default = true`
+pub fn default_monotonic(app: &App) -> Option<&Monotonic> {
+    app.monotonics
+        .values()
+        .find(|monotonic| monotonic.args.default)
 }
 
 /// Generates an identifier for the `enum` of `schedule`-able tasks
@@ -260,6 +699,117 @@ pub fn static_local_resource_ident(name: &Ident) -> Ident {
     mark_internal_name(&format!("local_resource_{}", name.to_string()))
 }
 
+/// Identifier of the salt of the check of a retained resource
+pub fn retained_salt_ident(name: &Ident) -> Ident {
+    mark_internal_name(&format!("{}_SALT", name))
+}
+
+/// The seed of the salt of the check of a retained resource, derived from its name and its type
+pub fn retained_seed(name: &Ident, ty: &Type) -> u32 {
+    // FNV-1a
+    format!("{}: {}", name, quote!(#ty))
+        .bytes()
+        .fold(0x811c_9dc5, |hash: u32, byte| {
+            (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+        })
+}
+
+/// Whether the local `name` declared by `task` is `#[lazy]`
+pub fn is_lazy_local(extra: &Extra, task: &Ident, name: &Ident) -> bool {
+    extra
+        .lazy_locals
+        .iter()
+        .any(|(task_, name_)| task_ == task && name_ == name)
+}
+
+/// Generates the storage of a retained resource, its value and the check over it in a
+/// `RetainedCell` the runtime doesn't initialize, and the salt of the check
+pub fn retained_storage(
+    name: &Ident,
+    mangled_name: &Ident,
+    ty: &Type,
+    cfgs: &[Attribute],
+    attrs: &[Attribute],
+) -> TokenStream2 {
+    let salt = retained_salt_ident(name);
+    let seed = retained_seed(name, ty);
+    let section = link_section_uninit();
+
+    quote!(
+        #[allow(non_camel_case_types)]
+        #[allow(non_upper_case_globals)]
+        #[doc(hidden)]
+        #(#attrs)*
+        #(#cfgs)*
+        #section
+        static #mangled_name: rtic::RacyCell<rtic::export::RetainedCell<#ty>> =
+            rtic::RacyCell::new(rtic::export::RetainedCell::uninit());
+
+        #[allow(non_upper_case_globals)]
+        #[doc(hidden)]
+        #(#cfgs)*
+        const #salt: u32 = rtic::export::retained_salt(
+            #seed,
+            env!("CARGO_PKG_VERSION"),
+            core::mem::size_of::<#ty>(),
+            core::mem::align_of::<#ty>(),
+        );
+
+        // A torn or stale value is only rejected if the check covers all of its bytes
+        #(#cfgs)*
+        const _: () = {
+            fn retainable<T: rtic::Retainable>() {}
+            let _ = retainable::<#ty>;
+        };
+    )
+}
+
+/// Updates the check of a retained resource after it was written
+pub fn retained_seal(name: &Ident, mangled_name: &Ident, cfgs: &[Attribute]) -> TokenStream2 {
+    let salt = retained_salt_ident(name);
+
+    quote!(
+        #(#cfgs)*
+        #mangled_name.get_mut_unchecked().seal(#salt);
+    )
+}
+
+/// Generates the statements that update the checks of the retained resources `task` gets by
+/// mutable reference without a lock, the `#[local]` and the `#[lock_free]` ones. They run once the
+/// task returns
+pub fn retained_reseal(app: &App, extra: &Extra, task: &Ident) -> Vec<TokenStream2> {
+    let (local, shared) = if let Some(task) = app.hardware_tasks.get(task) {
+        (&task.args.local_resources, &task.args.shared_resources)
+    } else if let Some(task) = app.software_tasks.get(task) {
+        (&task.args.local_resources, &task.args.shared_resources)
+    } else {
+        return vec![];
+    };
+
+    let local = local
+        .iter()
+        .filter(|(name, local)| {
+            matches!(local, TaskLocal::External) && extra.local_retained.contains(*name)
+        })
+        .map(|(name, _)| {
+            let res = &app.local_resources[name];
+            retained_seal(name, &static_local_resource_ident(name), &res.cfgs)
+        });
+    let shared = shared
+        .iter()
+        .filter(|(name, access)| {
+            extra.shared_retained.contains(*name)
+                && access.is_exclusive()
+                && app.shared_resources[*name].properties.lock_free
+        })
+        .map(|(name, _)| {
+            let res = &app.shared_resources[name];
+            retained_seal(name, &static_shared_resource_ident(name), &res.cfgs)
+        });
+
+    local.chain(shared).collect()
+}
+
 pub fn declared_static_local_resource_ident(name: &Ident, task_name: &Ident) -> Ident {
     mark_internal_name(&format!(
         "local_{}_{}",
@@ -10,6 +10,7 @@ use rtic_syntax::Settings;
 mod analyze;
 mod check;
 mod codegen;
+mod preprocess;
 #[cfg(test)]
 mod tests;
 
@@ -24,12 +25,17 @@ pub fn app(args: TokenStream, input: TokenStream) -> TokenStream {
     settings.parse_binds = true;
     settings.parse_extern_interrupt = true;
 
-    let (app, analysis) = match rtic_syntax::parse(args, input, settings) {
+    let (args, input, ext) = match preprocess::app(args.into(), input.into()) {
         Err(e) => return e.to_compile_error().into(),
         Ok(x) => x,
     };
 
-    let extra = match check::app(&app, &analysis) {
+    let (app, analysis) = match rtic_syntax::parse2(args, input, settings) {
+        Err(e) => return e.to_compile_error().into(),
+        Ok(x) => x,
+    };
+
+    let extra = match check::app(&app, &analysis, ext) {
         Err(e) => return e.to_compile_error().into(),
         Ok(x) => x,
     };
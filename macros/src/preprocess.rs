@@ -0,0 +1,833 @@
+//! Syntax extensions of the Cortex-M port
+//!
+//! `rtic-syntax` rejects arguments it doesn't know about, so the ones specific to this port are
+//! removed from the input before it's parsed and collected in `Extensions` instead
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use proc_macro2::{Delimiter, Group, TokenStream as TokenStream2, TokenTree};
+use quote::quote;
+use syn::{
+    bracketed,
+    parse::{self, Parse, ParseStream, Parser},
+    punctuated::Punctuated,
+    Attribute, Expr, FnArg, GenericArgument, Ident, Item, ItemFn, ItemMod, ItemStruct, ItemType,
+    LitInt, PathArguments, ReturnType, Token, Type,
+};
+
+/// Everything that was removed from the input
+#[derive(Default)]
+pub struct Extensions {
+    /// Hardware tasks declared with `#[task(zero_latency)]`
+    pub zero_latency: BTreeSet<Ident>,
+    /// The `#[pre_init]` function
+    pub pre_init: Option<ItemFn>,
+    /// The error type of a fallible `#[init]`
+    pub init_error: Option<Type>,
+    /// The `#[init_failed]` function
+    pub init_failed: Option<ItemFn>,
+    /// Software tasks that return a reply, and the type of that reply
+    pub replies: BTreeMap<Ident, Type>,
+    /// Software tasks that get their message by reference, `#[task(by_ref)]`, and the reference
+    /// type of the message
+    pub by_ref: BTreeMap<Ident, Type>,
+    /// The `#[event]` type declarations
+    pub events: Vec<ItemType>,
+    /// The events each software task subscribes to
+    pub subscriptions: BTreeMap<Ident, Vec<Ident>>,
+    /// Dispatchers declared as `dispatchers = [X = edf]`
+    pub edf_dispatchers: BTreeSet<Ident>,
+    /// The relative deadlines of software tasks
+    pub deadlines: BTreeMap<Ident, Expr>,
+    /// The `order` of software tasks within their priority level
+    pub orders: BTreeMap<Ident, u8>,
+    /// The operating modes, `#[rtic::app(modes = [..])]`
+    pub modes: Vec<Ident>,
+    /// The modes each task runs in
+    pub task_modes: BTreeMap<Ident, Vec<Ident>>,
+    /// The message pools and their capacity, `#[rtic::app(pools = [name = capacity])]`
+    pub pools: Vec<(Ident, u16)>,
+    /// The pool each software task takes its message slots from, `#[task(pool = name)]`
+    pub task_pools: BTreeMap<Ident, Ident>,
+    /// The capacities of software tasks that don't fit the `u8` of `rtic-syntax`
+    pub capacities: BTreeMap<Ident, u16>,
+    /// Software tasks whose messages are heap allocated, `#[task(capacity = unbounded)]`
+    pub unbounded: BTreeSet<Ident>,
+    /// The `#[on_mode_change]` function
+    pub on_mode_change: Option<ItemFn>,
+    /// The minimum time between two runs of rate limited hardware tasks
+    pub min_interarrivals: BTreeMap<Ident, Expr>,
+    /// The monotonics named by `#[task(monotonic = ..)]`
+    pub task_monotonics: BTreeMap<Ident, Ident>,
+    /// The execution budgets of tasks
+    pub budgets: BTreeMap<Ident, Expr>,
+    /// The `#[budget_overrun]` function
+    pub budget_overrun: Option<ItemFn>,
+    /// The windows of tasks supervised by the software watchdog
+    pub watchdogs: BTreeMap<Ident, Expr>,
+    /// The `#[on_watchdog_timeout]` function
+    pub on_watchdog_timeout: Option<ItemFn>,
+    /// The `#[watchdog_kick]` function
+    pub watchdog_kick: Option<ItemFn>,
+    /// The `#[fault]` function
+    pub fault: Option<ItemFn>,
+    /// Shared resources declared with a constant initializer, `#[init(..)]`
+    pub shared_inits: BTreeMap<Ident, Expr>,
+    /// Local resources declared with a constant initializer, `#[init(..)]`
+    pub local_inits: BTreeMap<Ident, Expr>,
+    /// Shared resources `#[init]` writes in place, `#[init(in_place)]`
+    pub shared_in_place: BTreeSet<Ident>,
+    /// Local resources `#[init]` writes in place, `#[init(in_place)]`
+    pub local_in_place: BTreeSet<Ident>,
+    /// Shared resources that survive a warm reset, `#[retain]`
+    pub shared_retained: BTreeSet<Ident>,
+    /// Local resources that survive a warm reset, `#[retain]`
+    pub local_retained: BTreeSet<Ident>,
+    /// Shared resources that are never mutated after `#[init]`, `#[frozen]`
+    pub frozen: BTreeSet<Ident>,
+    /// Locals declared `local = [#[lazy] name: T = expr]`: the task and the name of the local
+    pub lazy_locals: BTreeSet<(Ident, Ident)>,
+}
+
+/// A `key` or `key = value` argument of an attribute
+struct Arg {
+    key: Ident,
+    value: Option<TokenStream2>,
+    tokens: TokenStream2,
+}
+
+impl Arg {
+    // Parses the value of a `key = value` argument
+    fn value<P: Parser>(&self, parser: P, expected: &str) -> parse::Result<P::Output> {
+        match &self.value {
+            Some(value) => parser.parse2(value.clone()),
+            None => Err(parse::Error::new(self.key.span(), expected)),
+        }
+    }
+
+    fn flag(&self) -> parse::Result<()> {
+        if let Some(value) = &self.value {
+            Err(parse::Error::new_spanned(
+                value,
+                format!("`{}` doesn't take a value", self.key),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Strips the port specific syntax from `args` and `input`
+pub fn app(
+    args: TokenStream2,
+    input: TokenStream2,
+) -> parse::Result<(TokenStream2, TokenStream2, Extensions)> {
+    let mut ext = Extensions::default();
+
+    let args = app_args(args, &mut ext)?;
+
+    // Leave malformed input untouched, `rtic-syntax` produces better errors for it
+    let mut module = match syn::parse2::<ItemMod>(input.clone()) {
+        Ok(module) => module,
+        Err(_) => return Ok((args, input, ext)),
+    };
+
+    if let Some((_, items)) = &mut module.content {
+        let mut kept = vec![];
+        for item in items.drain(..) {
+            match item {
+                Item::Fn(mut item) => {
+                    if let Some(attr) = take_attr(&mut item.attrs, "pre_init") {
+                        hook(&mut ext.pre_init, attr, item)?;
+                        continue;
+                    }
+
+                    if let Some(attr) = take_attr(&mut item.attrs, "init_failed") {
+                        hook(&mut ext.init_failed, attr, item)?;
+                        continue;
+                    }
+
+                    if let Some(attr) = take_attr(&mut item.attrs, "on_mode_change") {
+                        hook(&mut ext.on_mode_change, attr, item)?;
+                        continue;
+                    }
+
+                    if let Some(attr) = take_attr(&mut item.attrs, "budget_overrun") {
+                        hook(&mut ext.budget_overrun, attr, item)?;
+                        continue;
+                    }
+
+                    if let Some(attr) = take_attr(&mut item.attrs, "on_watchdog_timeout") {
+                        hook(&mut ext.on_watchdog_timeout, attr, item)?;
+                        continue;
+                    }
+
+                    if let Some(attr) = take_attr(&mut item.attrs, "watchdog_kick") {
+                        hook(&mut ext.watchdog_kick, attr, item)?;
+                        continue;
+                    }
+
+                    if let Some(attr) = take_attr(&mut item.attrs, "fault") {
+                        hook(&mut ext.fault, attr, item)?;
+                        continue;
+                    }
+
+                    init(&mut item, &mut ext);
+                    task(&mut item, &mut ext)?;
+                    lazy_locals(&mut item, &mut ext)?;
+                    kept.push(Item::Fn(item));
+                }
+
+                Item::Type(mut item) => {
+                    if let Some(attr) = take_attr(&mut item.attrs, "event") {
+                        if !attr.tokens.is_empty() {
+                            return Err(parse::Error::new_spanned(
+                                attr.tokens,
+                                "`#[event]` doesn't take arguments",
+                            ));
+                        }
+
+                        ext.events.push(item);
+                        continue;
+                    }
+
+                    kept.push(Item::Type(item));
+                }
+
+                Item::Struct(mut item) => {
+                    resource_fields(&mut item, &mut ext)?;
+                    kept.push(Item::Struct(item));
+                }
+
+                item => kept.push(item),
+            }
+        }
+
+        *items = kept;
+    }
+
+    Ok((args, quote!(#module), ext))
+}
+
+// Strips the `modes` argument and `= edf` from the elements of `dispatchers = [..]`
+fn app_args(args: TokenStream2, ext: &mut Extensions) -> parse::Result<TokenStream2> {
+    let mut kept = vec![];
+    for mut segment in split_stream(args) {
+        let key = match segment.first() {
+            Some(TokenTree::Ident(key)) => key.to_string(),
+            _ => String::new(),
+        };
+
+        match &*key {
+            "modes" => {
+                let value = segment.into_iter().skip(2).collect::<TokenStream2>();
+                ext.modes = ident_list.parse2(value)?;
+                continue;
+            }
+
+            "pools" => {
+                let value = segment.into_iter().skip(2).collect::<TokenStream2>();
+                ext.pools = pool_list.parse2(value)?;
+                continue;
+            }
+
+            "dispatchers" => {
+                if let Some(TokenTree::Group(group)) = segment.get_mut(2) {
+                    if group.delimiter() == Delimiter::Bracket {
+                        *group = edf_dispatchers(group, ext);
+                    }
+                }
+            }
+
+            _ => {}
+        }
+
+        kept.push(segment.into_iter().collect::<TokenStream2>());
+    }
+
+    Ok(quote!(#(#kept),*))
+}
+
+// Strips `= edf` from the elements of `[..]`, the dispatchers list
+fn edf_dispatchers(group: &Group, ext: &mut Extensions) -> Group {
+    let mut dispatchers = vec![];
+    for mut segment in split_stream(group.stream()) {
+        let n = segment.len();
+        let is_edf = n >= 3
+            && matches!(&segment[n - 1], TokenTree::Ident(arg) if arg == "edf")
+            && matches!(&segment[n - 2], TokenTree::Punct(punct) if punct.as_char() == '=');
+
+        if is_edf {
+            if let TokenTree::Ident(name) = &segment[n - 3] {
+                ext.edf_dispatchers.insert(name.clone());
+                segment.truncate(n - 2);
+            }
+        }
+
+        dispatchers.push(segment.into_iter().collect::<TokenStream2>());
+    }
+
+    let mut stripped = Group::new(Delimiter::Bracket, quote!(#(#dispatchers),*));
+    stripped.set_span(group.span());
+    stripped
+}
+
+// Stores a function marked with an argument-less attribute, like `#[pre_init]`
+fn hook(slot: &mut Option<ItemFn>, attr: Attribute, item: ItemFn) -> parse::Result<()> {
+    let name = attr.path.get_ident().expect("UNREACHABLE");
+
+    if slot.is_some() {
+        return Err(parse::Error::new(
+            item.sig.ident.span(),
+            format!("`#[{}]` function must appear at most once", name),
+        ));
+    }
+
+    if !attr.tokens.is_empty() {
+        return Err(parse::Error::new_spanned(
+            attr.tokens,
+            format!("`#[{}]` doesn't take arguments", name),
+        ));
+    }
+
+    *slot = Some(item);
+
+    Ok(())
+}
+
+// Turns `fn init(..) -> Result<T, E>` into `fn init(..) -> T`, a fallible `#[init]`
+fn init(item: &mut ItemFn, ext: &mut Extensions) {
+    if !item.attrs.iter().any(|attr| attr.path.is_ident("init")) {
+        return;
+    }
+
+    let ty = match &mut item.sig.output {
+        ReturnType::Type(_, ty) => ty,
+        ReturnType::Default => return,
+    };
+
+    let args = match &**ty {
+        Type::Path(path) if path.qself.is_none() => match path.path.segments.last() {
+            Some(segment) if segment.ident == "Result" => &segment.arguments,
+            _ => return,
+        },
+        _ => return,
+    };
+
+    let (ok, err) = match args {
+        PathArguments::AngleBracketed(args) if args.args.len() == 2 => {
+            match (&args.args[0], &args.args[1]) {
+                (GenericArgument::Type(ok), GenericArgument::Type(err)) => {
+                    (ok.clone(), err.clone())
+                }
+                _ => return,
+            }
+        }
+        _ => return,
+    };
+
+    **ty = ok;
+    ext.init_error = Some(err);
+}
+
+// Strips `#[init(..)]`, `#[retain]` and `#[frozen]` from the fields of the `#[shared]` and
+// `#[local]` structs
+fn resource_fields(item: &mut ItemStruct, ext: &mut Extensions) -> parse::Result<()> {
+    let shared = if item.attrs.iter().any(|attr| attr.path.is_ident("shared")) {
+        true
+    } else if item.attrs.iter().any(|attr| attr.path.is_ident("local")) {
+        false
+    } else {
+        return Ok(());
+    };
+
+    for field in item.fields.iter_mut() {
+        let name = match &field.ident {
+            Some(name) => name.clone(),
+            None => continue,
+        };
+
+        if let Some(attr) = take_attr(&mut field.attrs, "init") {
+            let init = attr.parse_args::<Expr>()?;
+
+            // `#[init(in_place)]` isn't an initializer: `#[init]` writes the resource through
+            // `cx.uninit`
+            let in_place = match &init {
+                Expr::Path(path) => path.path.is_ident("in_place"),
+                _ => false,
+            };
+
+            if in_place {
+                if shared {
+                    ext.shared_in_place.insert(name.clone());
+                } else {
+                    ext.local_in_place.insert(name.clone());
+                }
+            } else if shared {
+                ext.shared_inits.insert(name.clone(), init);
+            } else {
+                ext.local_inits.insert(name.clone(), init);
+            }
+        }
+
+        if take_flag(&mut field.attrs, "retain")?.is_some() {
+            if shared {
+                ext.shared_retained.insert(name.clone());
+            } else {
+                ext.local_retained.insert(name.clone());
+            }
+        }
+
+        if let Some(attr) = take_flag(&mut field.attrs, "frozen")? {
+            if !shared {
+                return Err(parse::Error::new_spanned(
+                    attr,
+                    "only `#[shared]` resources can be `#[frozen]`",
+                ));
+            }
+
+            ext.frozen.insert(name);
+        }
+    }
+
+    Ok(())
+}
+
+// Strips the extension arguments of a `#[task]`
+fn task(item: &mut ItemFn, ext: &mut Extensions) -> parse::Result<()> {
+    let name = item.sig.ident.clone();
+    let attr = match item
+        .attrs
+        .iter_mut()
+        .find(|attr| attr.path.is_ident("task"))
+    {
+        Some(attr) => attr,
+        None => return Ok(()),
+    };
+
+    let args = split_args(attr)?;
+    let has_priority = args.iter().any(|arg| arg.key == "priority");
+    let has_binds = args.iter().any(|arg| arg.key == "binds");
+    let has_capacity = args.iter().any(|arg| arg.key == "capacity");
+
+    let mut by_ref = None;
+    let mut kept = vec![];
+    for arg in args {
+        match &*arg.key.to_string() {
+            "by_ref" => {
+                arg.flag()?;
+
+                if has_binds {
+                    return Err(parse::Error::new(
+                        arg.key.span(),
+                        "only software tasks get their message by reference",
+                    ));
+                }
+
+                by_ref = Some(arg.key);
+            }
+
+            "zero_latency" => {
+                arg.flag()?;
+
+                if has_priority {
+                    return Err(parse::Error::new(
+                        arg.key.span(),
+                        "zero latency tasks run above every priority; `priority` can't be used",
+                    ));
+                }
+
+                ext.zero_latency.insert(name.clone());
+            }
+
+            "subscribe" => {
+                if has_binds {
+                    return Err(parse::Error::new(
+                        arg.key.span(),
+                        "only software tasks can subscribe to events",
+                    ));
+                }
+
+                let events = arg.value(
+                    ident_list,
+                    "expected a list of events, e.g. `subscribe = [Event]`",
+                )?;
+
+                ext.subscriptions.insert(name.clone(), events);
+            }
+
+            "deadline" => {
+                let deadline = arg.value(
+                    Expr::parse,
+                    "expected a duration, e.g. `deadline = Milliseconds(10_u32)`",
+                )?;
+
+                ext.deadlines.insert(name.clone(), deadline);
+            }
+
+            "modes" => {
+                let modes = arg.value(
+                    ident_list,
+                    "expected a list of modes, e.g. `modes = [Normal]`",
+                )?;
+
+                ext.task_modes.insert(name.clone(), modes);
+            }
+
+            "min_interarrival" => {
+                let min_interarrival = arg.value(
+                    Expr::parse,
+                    "expected a duration, e.g. `min_interarrival = Milliseconds(1_u32)`",
+                )?;
+
+                ext.min_interarrivals.insert(name.clone(), min_interarrival);
+            }
+
+            "budget" => {
+                let budget = arg.value(
+                    Expr::parse,
+                    "expected a duration, e.g. `budget = Microseconds(200_u32)`",
+                )?;
+
+                ext.budgets.insert(name.clone(), budget);
+            }
+
+            "watchdog" => {
+                let window = arg.value(
+                    Expr::parse,
+                    "expected a duration, e.g. `watchdog = Milliseconds(500_u32)`",
+                )?;
+
+                ext.watchdogs.insert(name.clone(), window);
+            }
+
+            "monotonic" => {
+                let monotonic = arg.value(
+                    Ident::parse,
+                    "expected the name of a monotonic, e.g. `monotonic = MyMono`",
+                )?;
+
+                ext.task_monotonics.insert(name.clone(), monotonic);
+            }
+
+            // `rtic-syntax` only knows numeric capacities, the task gets the default one
+            "capacity"
+                if arg
+                    .value
+                    .as_ref()
+                    .map_or(false, |value| value.to_string() == "unbounded") =>
+            {
+                if has_binds {
+                    return Err(parse::Error::new(
+                        arg.key.span(),
+                        "only software tasks have a `capacity`",
+                    ));
+                }
+
+                if !cfg!(feature = "alloc") {
+                    return Err(parse::Error::new_spanned(
+                        arg.tokens,
+                        "`capacity = unbounded` requires the `alloc` feature of `cortex-m-rtic`",
+                    ));
+                }
+
+                ext.unbounded.insert(name.clone());
+            }
+
+            // Capacities above 255 make the slot indices `u16`, the task gets the default one
+            "capacity"
+                if arg.value.as_ref().map_or(false, |value| {
+                    syn::parse2::<LitInt>(value.clone())
+                        .map_or(false, |lit| lit.base10_parse::<u8>().is_err())
+                }) =>
+            {
+                if has_binds {
+                    return Err(parse::Error::new(
+                        arg.key.span(),
+                        "only software tasks have a `capacity`",
+                    ));
+                }
+
+                let capacity = arg.value(
+                    |input: ParseStream| input.parse::<LitInt>()?.base10_parse::<u16>(),
+                    "expected a capacity",
+                )?;
+
+                ext.capacities.insert(name.clone(), capacity);
+            }
+
+            "pool" => {
+                if has_binds {
+                    return Err(parse::Error::new(
+                        arg.key.span(),
+                        "only software tasks take their message slots from a pool",
+                    ));
+                }
+
+                if has_capacity {
+                    return Err(parse::Error::new(
+                        arg.key.span(),
+                        "tasks that use a pool share its capacity; `capacity` can't be used",
+                    ));
+                }
+
+                let pool = arg.value(
+                    Ident::parse,
+                    "expected the name of a pool, e.g. `pool = frames`",
+                )?;
+
+                ext.task_pools.insert(name.clone(), pool);
+            }
+
+            "order" => {
+                let order = arg
+                    .value(
+                        LitInt::parse,
+                        "expected an integer in the range 0..=255, e.g. `order = 0`",
+                    )?
+                    .base10_parse::<u8>()?;
+
+                ext.orders.insert(name.clone(), order);
+            }
+
+            _ => kept.push(arg.tokens),
+        }
+    }
+
+    set_args(attr, kept);
+
+    // The message stays in the buffer of the task, `rtic-syntax` sees the type it's stored as
+    if let Some(key) = by_ref {
+        let input = match item.sig.inputs.iter_mut().nth(1) {
+            Some(FnArg::Typed(input)) if item.sig.inputs.len() == 2 => input,
+            _ => {
+                return Err(parse::Error::new(
+                    key.span(),
+                    "`by_ref` tasks take a single message, e.g. `msg: &Msg`",
+                ))
+            }
+        };
+
+        let elem = match &*input.ty {
+            Type::Reference(reference) => (*reference.elem).clone(),
+            ty => {
+                return Err(parse::Error::new_spanned(
+                    ty,
+                    "`by_ref` tasks take their message by reference, `&Msg` or `&mut Msg`",
+                ))
+            }
+        };
+
+        ext.by_ref.insert(
+            name.clone(),
+            *std::mem::replace(&mut input.ty, Box::new(elem)),
+        );
+    }
+
+    // Software tasks may return a reply for the task given to `call`
+    if !has_binds {
+        if let ReturnType::Type(_, ty) = &item.sig.output {
+            ext.replies.insert(name, (**ty).clone());
+            item.sig.output = ReturnType::Default;
+        }
+    }
+
+    Ok(())
+}
+
+// Strips `#[lazy]` from the locals declared in the `local = [..]` argument of `#[init]`, `#[idle]`
+// and `#[task]`
+fn lazy_locals(item: &mut ItemFn, ext: &mut Extensions) -> parse::Result<()> {
+    let task = item.sig.ident.clone();
+    let attr = match item.attrs.iter_mut().find(|attr| {
+        ["init", "idle", "task"]
+            .iter()
+            .any(|name| attr.path.is_ident(name))
+    }) {
+        Some(attr) => attr,
+        None => return Ok(()),
+    };
+
+    let mut kept = vec![];
+    for arg in split_args(attr)? {
+        let group = match arg.value.clone().and_then(|value| value.into_iter().next()) {
+            Some(TokenTree::Group(group))
+                if arg.key == "local" && group.delimiter() == Delimiter::Bracket =>
+            {
+                group
+            }
+
+            // Leave anything else to `rtic-syntax`
+            _ => {
+                kept.push(arg.tokens);
+                continue;
+            }
+        };
+
+        let mut locals = vec![];
+        for mut local in split_stream(group.stream()) {
+            // The attributes come first, `#` followed by `[..]`
+            let mut i = 0;
+            while let (Some(TokenTree::Punct(pound)), Some(TokenTree::Group(bracket))) =
+                (local.get(i).cloned(), local.get(i + 1).cloned())
+            {
+                if pound.as_char() != '#' || bracket.delimiter() != Delimiter::Bracket {
+                    break;
+                }
+
+                if bracket.stream().to_string() == "lazy" {
+                    local.drain(i..i + 2);
+                    let name = match (local.get(i), local.get(i + 1)) {
+                        (Some(TokenTree::Ident(name)), Some(TokenTree::Punct(colon)))
+                            if colon.as_char() == ':' =>
+                        {
+                            name.clone()
+                        }
+
+                        _ => {
+                            return Err(parse::Error::new_spanned(
+                                bracket,
+                                "`#[lazy]` only applies to locals declared as `name: T = expr`",
+                            ))
+                        }
+                    };
+
+                    ext.lazy_locals.insert((task.clone(), name));
+                } else {
+                    i += 2;
+                }
+            }
+
+            locals.push(local.into_iter().collect::<TokenStream2>());
+        }
+
+        let key = &arg.key;
+        let mut stripped = Group::new(Delimiter::Bracket, quote!(#(#locals),*));
+        stripped.set_span(group.span());
+        kept.push(quote!(#key = #stripped));
+    }
+
+    set_args(attr, kept);
+
+    Ok(())
+}
+
+// Removes the `#[name]` attribute from `attrs`
+fn take_attr(attrs: &mut Vec<Attribute>, name: &str) -> Option<Attribute> {
+    let pos = attrs.iter().position(|attr| attr.path.is_ident(name))?;
+
+    Some(attrs.remove(pos))
+}
+
+// Removes the argument-less attribute `#[name]` from `attrs`
+fn take_flag(attrs: &mut Vec<Attribute>, name: &str) -> parse::Result<Option<Attribute>> {
+    let attr = match take_attr(attrs, name) {
+        Some(attr) => attr,
+        None => return Ok(None),
+    };
+
+    if !attr.tokens.is_empty() {
+        return Err(parse::Error::new_spanned(
+            attr.tokens,
+            format!("`#[{}]` doesn't take arguments", name),
+        ));
+    }
+
+    Ok(Some(attr))
+}
+
+// Splits the arguments of `#[attr(a, b = c, ..)]` at the commas
+fn split_args(attr: &Attribute) -> parse::Result<Vec<Arg>> {
+    let stream = match attr.tokens.clone().into_iter().next() {
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis => {
+            group.stream()
+        }
+
+        // `#[attr]`, nothing to split
+        _ => return Ok(vec![]),
+    };
+
+    split_stream(stream)
+        .into_iter()
+        .map(|segment| {
+            let tokens = segment.iter().cloned().collect::<TokenStream2>();
+            let mut iter = segment.into_iter();
+
+            let key = match iter.next() {
+                Some(TokenTree::Ident(key)) => key,
+                _ => {
+                    return Err(parse::Error::new_spanned(
+                        &tokens,
+                        "expected an argument of the form `key` or `key = value`",
+                    ))
+                }
+            };
+
+            let value = match iter.next() {
+                None => None,
+                Some(TokenTree::Punct(punct)) if punct.as_char() == '=' => {
+                    Some(iter.collect::<TokenStream2>())
+                }
+                Some(tt) => {
+                    return Err(parse::Error::new_spanned(tt, "expected `=` or `,`"));
+                }
+            };
+
+            Ok(Arg { key, value, tokens })
+        })
+        .collect()
+}
+
+// Splits `stream` at its top level commas, dropping empty segments
+fn split_stream(stream: TokenStream2) -> Vec<Vec<TokenTree>> {
+    let mut segments = vec![vec![]];
+    for tt in stream {
+        match &tt {
+            TokenTree::Punct(punct) if punct.as_char() == ',' => segments.push(vec![]),
+            _ => segments.last_mut().expect("UNREACHABLE").push(tt),
+        }
+    }
+
+    segments.retain(|segment| !segment.is_empty());
+    segments
+}
+
+// Parses `[a, b, ..]`
+fn ident_list(input: ParseStream) -> parse::Result<Vec<Ident>> {
+    let content;
+    bracketed!(content in input);
+
+    let idents = Punctuated::<Ident, Token![,]>::parse_terminated(&content)?;
+
+    Ok(idents.into_iter().collect())
+}
+
+// Parses `[a = 4, b = 8, ..]`, the pools and their capacity
+fn pool_list(input: ParseStream) -> parse::Result<Vec<(Ident, u16)>> {
+    let content;
+    bracketed!(content in input);
+
+    let mut pools = vec![];
+    while !content.is_empty() {
+        let name = content.parse::<Ident>()?;
+        content.parse::<Token![=]>()?;
+        let capacity = content.parse::<LitInt>()?.base10_parse::<u16>()?;
+        pools.push((name, capacity));
+
+        if content.is_empty() {
+            break;
+        }
+        content.parse::<Token![,]>()?;
+    }
+
+    Ok(pools)
+}
+
+// Replaces the arguments of `attr` with the `kept` ones
+fn set_args(attr: &mut Attribute, kept: Vec<TokenStream2>) {
+    attr.tokens = if kept.is_empty() {
+        TokenStream2::new()
+    } else {
+        quote!((#(#kept),*))
+    };
+}
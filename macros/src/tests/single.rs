@@ -38,3 +38,147 @@ fn analyze() {
     assert_eq!(interrupts[&2].0.to_string(), "B");
     assert_eq!(interrupts[&1].0.to_string(), "A");
 }
+
+#[test]
+fn preprocess() {
+    let (_, input, ext) = crate::preprocess::app(
+        quote!(device = pac),
+        quote!(
+            mod app {
+                #[task(binds = A, zero_latency, shared = [x])]
+                fn a(_: a::Context) {}
+            }
+        ),
+    )
+    .unwrap();
+
+    assert!(ext.zero_latency.iter().any(|name| name == "a"));
+    assert!(!input.to_string().contains("zero_latency"));
+}
+
+#[test]
+fn preprocess_resource_fields() {
+    let (_, input, ext) = crate::preprocess::app(
+        quote!(device = pac),
+        quote!(
+            mod app {
+                #[shared]
+                struct Shared {
+                    #[init(0)]
+                    x: u32,
+                    #[retain]
+                    y: u32,
+                    #[frozen]
+                    w: u32,
+                    #[init(in_place)]
+                    v: [u8; 4096],
+                }
+
+                #[local]
+                struct Local {
+                    #[init([0; 4])]
+                    z: [u8; 4],
+                }
+            }
+        ),
+    )
+    .unwrap();
+
+    assert!(ext.shared_inits.keys().any(|name| name == "x"));
+    assert!(!ext.shared_inits.keys().any(|name| name == "y"));
+    assert!(ext.local_inits.keys().any(|name| name == "z"));
+    assert!(ext.shared_retained.iter().any(|name| name == "y"));
+    assert!(ext.frozen.iter().any(|name| name == "w"));
+    assert!(ext.shared_in_place.iter().any(|name| name == "v"));
+    assert!(!ext.shared_inits.keys().any(|name| name == "v"));
+    assert!(!input.to_string().contains("# [init"));
+    assert!(!input.to_string().contains("# [retain"));
+    assert!(!input.to_string().contains("# [frozen"));
+}
+
+#[test]
+fn preprocess_lazy_locals() {
+    let (_, input, ext) = crate::preprocess::app(
+        quote!(device = pac),
+        quote!(
+            mod app {
+                #[task(local = [#[lazy] a: Vec<u8, 4> = Vec::new(), b: u32 = 0])]
+                fn foo(_: foo::Context) {}
+            }
+        ),
+    )
+    .unwrap();
+
+    assert!(ext
+        .lazy_locals
+        .iter()
+        .any(|(task, name)| task == "foo" && name == "a"));
+    assert!(!ext.lazy_locals.iter().any(|(_, name)| name == "b"));
+    assert!(!input.to_string().contains("lazy"));
+}
+
+#[test]
+fn preprocess_by_ref() {
+    let (_, input, ext) = crate::preprocess::app(
+        quote!(device = pac),
+        quote!(
+            mod app {
+                #[task(capacity = 2, by_ref)]
+                fn foo(_: foo::Context, frame: &Frame) {}
+            }
+        ),
+    )
+    .unwrap();
+
+    assert!(ext.by_ref.keys().any(|name| name == "foo"));
+    assert!(!input.to_string().contains("by_ref"));
+    assert!(!input.to_string().contains("& Frame"));
+}
+
+#[test]
+fn preprocess_unbounded() {
+    let result = crate::preprocess::app(
+        quote!(device = pac),
+        quote!(
+            mod app {
+                #[task(capacity = unbounded)]
+                fn foo(_: foo::Context, _: u32) {}
+            }
+        ),
+    );
+
+    if cfg!(feature = "alloc") {
+        let (_, input, ext) = result.unwrap();
+
+        assert!(ext.unbounded.iter().any(|name| name == "foo"));
+        assert!(!input.to_string().contains("unbounded"));
+    } else {
+        assert!(result.is_err());
+    }
+}
+
+#[test]
+fn preprocess_big_capacity() {
+    let (_, input, ext) = crate::preprocess::app(
+        quote!(device = pac),
+        quote!(
+            mod app {
+                #[task(capacity = 512)]
+                fn foo(_: foo::Context, _: u32) {}
+
+                #[task(capacity = 4)]
+                fn bar(_: bar::Context, _: u32) {}
+            }
+        ),
+    )
+    .unwrap();
+
+    // Only the capacities `rtic-syntax` can't parse are taken out
+    assert_eq!(ext.capacities.len(), 1);
+    assert!(ext
+        .capacities
+        .iter()
+        .any(|(name, cap)| name == "foo" && *cap == 512));
+    assert!(!input.to_string().contains("512"));
+    assert!(input.to_string().contains("capacity = 4"));
+}
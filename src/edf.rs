@@ -0,0 +1,92 @@
+use crate::{time::Instant, Monotonic};
+use core::cmp::Ordering;
+use heapless::sorted_linked_list::{LinkedIndexU16, Min, SortedLinkedList};
+
+/// Ready queue of a dispatcher that runs its tasks in order of their absolute deadline
+pub struct EdfQueue<Mono, Task, Index, const N: usize>(
+    pub SortedLinkedList<Deadline<Mono, Task, Index>, LinkedIndexU16, Min, N>,
+)
+where
+    Mono: Monotonic,
+    Task: Copy;
+
+impl<Mono, Task, Index, const N: usize> EdfQueue<Mono, Task, Index, N>
+where
+    Mono: Monotonic,
+    Task: Copy,
+    Index: Copy,
+{
+    /// # Safety
+    ///
+    /// Enqueue a task without checking if it is full
+    #[inline]
+    pub unsafe fn enqueue_unchecked(
+        &mut self,
+        (task, index): (Task, Index),
+        deadline: Instant<Mono>,
+    ) {
+        self.0.push_unchecked(Deadline {
+            index,
+            deadline,
+            task,
+        });
+    }
+
+    /// Dequeue the task with the earliest deadline
+    #[inline]
+    pub fn dequeue(&mut self) -> Option<(Task, Index)> {
+        self.0.pop().ok().map(|d| (d.task, d.index))
+    }
+}
+
+pub struct Deadline<Mono, Task, Index>
+where
+    Task: Copy,
+    Index: Copy,
+    Mono: Monotonic,
+{
+    pub index: Index,
+    pub deadline: Instant<Mono>,
+    pub task: Task,
+}
+
+impl<Mono, Task, Index> Eq for Deadline<Mono, Task, Index>
+where
+    Task: Copy,
+    Index: Copy,
+    Mono: Monotonic,
+{
+}
+
+impl<Mono, Task, Index> Ord for Deadline<Mono, Task, Index>
+where
+    Task: Copy,
+    Index: Copy,
+    Mono: Monotonic,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.deadline.cmp(&other.deadline)
+    }
+}
+
+impl<Mono, Task, Index> PartialEq for Deadline<Mono, Task, Index>
+where
+    Task: Copy,
+    Index: Copy,
+    Mono: Monotonic,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.deadline == other.deadline
+    }
+}
+
+impl<Mono, Task, Index> PartialOrd for Deadline<Mono, Task, Index>
+where
+    Task: Copy,
+    Index: Copy,
+    Mono: Monotonic,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
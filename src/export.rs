@@ -3,12 +3,18 @@ use core::{
     sync::atomic::{AtomicBool, Ordering},
 };
 
+pub use crate::edf::{Deadline, EdfQueue};
+pub use crate::retained::{retained_salt, RetainedCell};
+pub use crate::task::{with_task, TaskFrame};
 pub use crate::tq::{NotReady, TimerQueue};
 pub use bare_metal::CriticalSection;
 pub use cortex_m::{
     asm::wfi,
     interrupt,
-    peripheral::{scb::SystemHandler, DWT, NVIC, SCB, SYST},
+    peripheral::{
+        scb::{Exception, SystemHandler, VectActive},
+        DWT, NVIC, SCB, SYST,
+    },
     Peripherals,
 };
 pub use heapless::sorted_linked_list::SortedLinkedList;
@@ -16,11 +22,95 @@ pub use heapless::spsc::Queue;
 pub use heapless::BinaryHeap;
 pub use rtic_monotonic as monotonic;
 
-pub type SCFQ<const N: usize> = Queue<u8, N>;
-pub type SCRQ<T, const N: usize> = Queue<(T, u8), N>;
+/// The slot indices are `u8`, or `u16` when the capacity of a task or pool exceeds 255
+pub type SCFQ<I, const N: usize> = Queue<I, N>;
+pub type SCRQ<T, I, const N: usize> = Queue<(T, I), N>;
+
+/// The heap allocated messages of a `capacity = unbounded` task, a linked list of nodes
+#[cfg(feature = "alloc")]
+pub struct UnboundedQueue<T> {
+    // Null when the task has no entry in its ready queue
+    head: *mut UnboundedNode<T>,
+    tail: *mut UnboundedNode<T>,
+}
+
+/// A message of a `capacity = unbounded` task on the heap
+#[cfg(feature = "alloc")]
+pub struct UnboundedNode<T> {
+    message: T,
+    next: *mut UnboundedNode<T>,
+}
+
+#[cfg(feature = "alloc")]
+impl<T> UnboundedNode<T> {
+    /// Moves `message` to the heap, `Err(message)` when there's no memory for it
+    ///
+    /// Called outside of any critical section, only linking the node takes one
+    pub fn new(message: T) -> Result<alloc::boxed::Box<Self>, T> {
+        let layout = core::alloc::Layout::new::<Self>();
+        // NOTE(unsafe) the layout isn't zero sized, there's at least the `next` pointer
+        let node = unsafe { alloc::alloc::alloc(layout) } as *mut Self;
+        if node.is_null() {
+            return Err(message);
+        }
+
+        // NOTE(unsafe) `node` was allocated by the global allocator with the layout of `Self`
+        unsafe {
+            node.write(UnboundedNode {
+                message,
+                next: core::ptr::null_mut(),
+            });
+
+            Ok(alloc::boxed::Box::from_raw(node))
+        }
+    }
+
+    /// Takes the message out of the node and frees it
+    pub fn into_message(self: alloc::boxed::Box<Self>) -> T {
+        self.message
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> UnboundedQueue<T> {
+    pub const fn new() -> Self {
+        UnboundedQueue {
+            head: core::ptr::null_mut(),
+            tail: core::ptr::null_mut(),
+        }
+    }
+
+    /// Appends `node`, `true` when the task must be put in the ready queue
+    pub fn push(&mut self, node: alloc::boxed::Box<UnboundedNode<T>>) -> bool {
+        let node = alloc::boxed::Box::into_raw(node);
+        let enqueue = self.head.is_null();
+        if enqueue {
+            self.head = node;
+        } else {
+            // NOTE(unsafe) `tail` is the last node of the list, which isn't empty
+            unsafe { (*self.tail).next = node }
+        }
+        self.tail = node;
+
+        enqueue
+    }
+
+    /// Unlinks the oldest message, and whether the task must stay in the ready queue for the next
+    /// one. The node is freed by `UnboundedNode::into_message`, outside of the critical section
+    ///
+    /// # Safety
+    ///
+    /// Must only be called for the entry of the task in the ready queue, there's then a message
+    pub unsafe fn pop(&mut self) -> (alloc::boxed::Box<UnboundedNode<T>>, bool) {
+        let node = alloc::boxed::Box::from_raw(self.head);
+        self.head = node.next;
+
+        (node, !self.head.is_null())
+    }
+}
 
 #[cfg(armv7m)]
-use cortex_m::register::basepri;
+use cortex_m::register::{basepri, basepri_max};
 
 #[cfg(armv7m)]
 #[inline(always)]
@@ -100,6 +190,42 @@ impl Priority {
     }
 }
 
+/// Whether the `NonMaskableInt` or the `HardFault` handler is currently running
+///
+/// These exceptions preempt every critical section, so the queues used by `spawn` must not be
+/// touched from them
+#[inline(always)]
+pub fn in_fixed_priority_exception() -> bool {
+    matches!(
+        SCB::vect_active(),
+        VectActive::Exception(Exception::NonMaskableInt)
+            | VectActive::Exception(Exception::HardFault)
+    )
+}
+
+/// Enables or disables a configurable fault handler through its `bit` in the SHCSR register
+///
+/// # Safety
+///
+/// Must be called in the critical section that protects the queues, the read-modify-write of
+/// SHCSR can't be interrupted
+#[inline]
+pub unsafe fn set_system_handler_enabled(bit: u32, enabled: bool) {
+    (*SCB::PTR).shcsr.modify(|r| {
+        if enabled {
+            r | (1 << bit)
+        } else {
+            r & !(1 << bit)
+        }
+    })
+}
+
+/// Whether the configurable fault handler with the SHCSR enable `bit` is enabled
+#[inline]
+pub fn is_system_handler_enabled(bit: u32) -> bool {
+    unsafe { (*SCB::PTR).shcsr.read() & (1 << bit) != 0 }
+}
+
 #[inline(always)]
 pub fn assert_send<T>()
 where
@@ -186,6 +312,55 @@ pub unsafe fn lock<T, R>(
     }
 }
 
+/// Token of the critical section of `free_below_zero_latency`
+///
+/// Not a `CriticalSection`: the zero latency tasks keep running inside it
+#[cfg(armv7m)]
+pub struct BelowZeroLatency {
+    _private: (),
+}
+
+/// Critical section that masks every priority except the one of the zero latency tasks
+///
+/// Used instead of `interrupt::free` when the application has zero latency tasks; these must never
+/// be blocked and they don't touch any of the data this protects
+#[cfg(armv7m)]
+#[inline(always)]
+pub fn free_below_zero_latency<F, R, const NVIC_PRIO_BITS: u8>(f: F) -> R
+where
+    F: FnOnce(&BelowZeroLatency) -> R,
+{
+    let initial = basepri::read();
+    unsafe {
+        basepri_max::write(logical2hw((1 << NVIC_PRIO_BITS) - 1, NVIC_PRIO_BITS));
+        let r = f(&BelowZeroLatency { _private: () });
+        basepri::write(initial);
+        r
+    }
+}
+
+/// Zero latency tasks run above the priorities masked through BASEPRI, which ARMv6-M doesn't have
+#[cfg(armv7m)]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __rtic_assert_zero_latency_supported {
+    () => {};
+}
+
+/// Zero latency tasks run above the priorities masked through BASEPRI, which ARMv6-M doesn't have
+#[cfg(not(armv7m))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __rtic_assert_zero_latency_supported {
+    () => {
+        compile_error!(
+            "zero latency tasks need the BASEPRI register, ARMv6-M targets don't have it"
+        );
+    };
+}
+
+pub use crate::__rtic_assert_zero_latency_supported as assert_zero_latency_supported;
+
 #[inline]
 pub fn logical2hw(logical: u8, nvic_prio_bits: u8) -> u8 {
     ((1 << nvic_prio_bits) - logical) << (8 - nvic_prio_bits)
@@ -0,0 +1,67 @@
+use core::{
+    mem::MaybeUninit,
+    ptr::{self, NonNull},
+};
+
+/// Proof that `#[init]` wrote a resource declared `#[init(in_place)]` to its storage
+///
+/// `#[init]` returns it in the place of the resource, which makes sure the storage,
+/// `cx.uninit.<name>`, was written before the resource is used.
+pub struct InPlace<T: 'static> {
+    ptr: NonNull<T>,
+}
+
+impl<T> InPlace<T> {
+    /// Initializes `slot` through `f`, which gets the slot and returns a reference to the value it
+    /// wrote there
+    ///
+    /// # Panics
+    ///
+    /// If `f` returns a reference to anything but `slot`
+    pub fn init_with<F>(slot: &'static mut MaybeUninit<T>, f: F) -> Self
+    where
+        F: for<'a> FnOnce(&'a mut MaybeUninit<T>) -> &'a mut T,
+    {
+        let expected = slot.as_ptr();
+        let value: *const T = f(slot);
+        assert!(
+            ptr::eq(value, expected),
+            "`InPlace::init_with` must return a reference to the slot it was given"
+        );
+
+        InPlace {
+            // NOTE(unsafe) the pointer comes from a reference
+            ptr: unsafe { NonNull::new_unchecked(value as *mut T) },
+        }
+    }
+
+    /// Asserts that `slot` was initialized, e.g. field by field through `slot.as_mut_ptr()`
+    ///
+    /// # Safety
+    ///
+    /// `slot` must hold a valid `T`
+    pub unsafe fn assume_init(slot: &'static mut MaybeUninit<T>) -> Self {
+        InPlace {
+            ptr: NonNull::new_unchecked(slot.as_mut_ptr()),
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn as_ptr(&self) -> *const T {
+        self.ptr.as_ptr()
+    }
+}
+
+impl<T, const N: usize> InPlace<[T; N]> {
+    /// Initializes the array in `slot` one element at a time, the `i`-th one is `f(i)`
+    pub fn from_fn(slot: &'static mut MaybeUninit<[T; N]>, mut f: impl FnMut(usize) -> T) -> Self {
+        let first = slot.as_mut_ptr() as *mut T;
+        for i in 0..N {
+            // NOTE(unsafe) `i` is in bounds
+            unsafe { first.add(i).write(f(i)) }
+        }
+
+        // NOTE(unsafe) every element was written above
+        unsafe { Self::assume_init(slot) }
+    }
+}
@@ -18,7 +18,8 @@
 //! # Minimum Supported Rust Version (MSRV)
 //!
 //! This crate is guaranteed to compile on stable Rust 1.36 (2018 edition) and up. It *might*
-//! compile on older versions but that may change in any new patch release.
+//! compile on older versions but that may change in any new patch release. The `alloc` feature
+//! has the same MSRV, it only needs the `alloc` crate.
 //!
 //! # Semantic Versioning
 //!
@@ -35,16 +36,31 @@
 #![deny(warnings)]
 #![no_std]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 use cortex_m::{interrupt::InterruptNumber, peripheral::NVIC};
 pub use cortex_m_rtic_macros::app;
 pub use rtic_core::{prelude as mutex_prelude, Exclusive, Mutex};
 pub use rtic_monotonic::{self, embedded_time as time, Monotonic};
 
+#[doc(hidden)]
+mod edf;
 #[doc(hidden)]
 pub mod export;
+mod in_place;
+mod retained;
+mod task;
 #[doc(hidden)]
 mod tq;
 
+pub use in_place::InPlace;
+pub use retained::Retainable;
+pub use task::{
+    current_task, preemption_chain, FaultInfo, PreemptionChain, ResourceAccess, TaskInfo, TaskKind,
+    TaskMetadata,
+};
+
 /// Sets the given `interrupt` as pending
 ///
 /// This is a convenience function around
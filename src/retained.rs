@@ -0,0 +1,185 @@
+use core::{
+    mem::{self, MaybeUninit},
+    ptr,
+};
+
+/// Types that `#[retain]` resources can have
+///
+/// After a warm reset the value is read back from memory the runtime didn't initialize, and a
+/// reset in the middle of a write leaves a value that's part old and part new. The check over its
+/// bytes catches the latter, but only if all of them are covered.
+///
+/// # Safety
+///
+/// Every bit pattern must be a valid value of the type and the type must have no padding bytes,
+/// e.g. integers, floats, arrays of them, or `#[repr(C)]` structs of them without padding.
+pub unsafe trait Retainable: Copy {}
+
+macro_rules! retainable {
+    ($($ty:ty),*) => {
+        $(unsafe impl Retainable for $ty {})*
+    };
+}
+
+retainable!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+unsafe impl<T: Retainable, const N: usize> Retainable for [T; N] {}
+
+/// The storage of a `#[retain]` resource: the value and a CRC-32 of its bytes, kept together in a
+/// section the runtime doesn't initialize
+#[doc(hidden)]
+#[repr(C)]
+pub struct RetainedCell<T> {
+    value: MaybeUninit<T>,
+    check: MaybeUninit<u32>,
+}
+
+impl<T> RetainedCell<T> {
+    /// A cell that holds nothing, the section it's placed in isn't initialized anyway
+    pub const fn uninit() -> Self {
+        RetainedCell {
+            value: MaybeUninit::uninit(),
+            check: MaybeUninit::uninit(),
+        }
+    }
+
+    /// Pointer to the value
+    pub fn as_ptr(&self) -> *const T {
+        self.value.as_ptr()
+    }
+
+    /// Mutable pointer to the value
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.value.as_mut_ptr()
+    }
+}
+
+impl<T: Retainable> RetainedCell<T> {
+    /// The value, if the check matches its bytes and `salt`
+    ///
+    /// # Safety
+    ///
+    /// Nothing may write to the cell meanwhile
+    pub unsafe fn read(&self, salt: u32) -> Option<T> {
+        if ptr::read_volatile(self.check.as_ptr()) == self.crc(salt) {
+            Some(ptr::read_volatile(self.value.as_ptr()))
+        } else {
+            None
+        }
+    }
+
+    /// Updates the check after the value was written
+    ///
+    /// # Safety
+    ///
+    /// The value must be initialized and nothing may access the cell meanwhile
+    pub unsafe fn seal(&mut self, salt: u32) {
+        let crc = self.crc(salt);
+        ptr::write_volatile(self.check.as_mut_ptr(), crc);
+    }
+
+    // CRC-32 (IEEE) of the bytes of the value, starting from `salt`
+    unsafe fn crc(&self, salt: u32) -> u32 {
+        let bytes = self.value.as_ptr() as *const u8;
+
+        !(0..mem::size_of::<T>()).fold(!salt, |crc, i| {
+            let crc = crc ^ u32::from(ptr::read_volatile(bytes.add(i)));
+
+            (0..8).fold(crc, |crc, _| {
+                if crc & 1 == 0 {
+                    crc >> 1
+                } else {
+                    (crc >> 1) ^ 0xedb8_8320
+                }
+            })
+        })
+    }
+}
+
+/// The salt of the check of a `#[retain]` resource
+///
+/// It mixes `seed`, derived from the name and the type of the resource, with the size and the
+/// alignment of the type and the version of the application, so that a firmware update that
+/// changes any of them doesn't pick up the value the previous firmware left
+#[doc(hidden)]
+pub const fn retained_salt(seed: u32, version: &str, size: usize, align: usize) -> u32 {
+    // FNV-1a
+    const fn mix(hash: u32, byte: u8) -> u32 {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    }
+
+    let mut hash = seed;
+
+    let version = version.as_bytes();
+    let mut i = 0;
+    while i < version.len() {
+        hash = mix(hash, version[i]);
+        i += 1;
+    }
+
+    let layout = [size as u32, align as u32];
+    let mut i = 0;
+    while i < layout.len() {
+        let mut shift = 0;
+        while shift < 32 {
+            hash = mix(hash, (layout[i] >> shift) as u8);
+            shift += 8;
+        }
+        i += 1;
+    }
+
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RetainedCell;
+
+    fn sealed(value: [u32; 4], salt: u32) -> RetainedCell<[u32; 4]> {
+        let mut cell = RetainedCell::<[u32; 4]>::uninit();
+
+        unsafe {
+            cell.as_mut_ptr().write(value);
+            cell.seal(salt);
+        }
+
+        cell
+    }
+
+    #[test]
+    fn sealed_value_is_read_back() {
+        let cell = sealed([1, 2, 3, 4], 42);
+
+        assert_eq!(unsafe { cell.read(42) }, Some([1, 2, 3, 4]));
+    }
+
+    #[test]
+    fn corrupted_check_is_rejected() {
+        let mut cell = sealed([1, 2, 3, 4], 42);
+
+        unsafe {
+            *cell.check.as_mut_ptr() ^= 1;
+
+            assert_eq!(cell.read(42), None);
+        }
+    }
+
+    #[test]
+    fn torn_value_is_rejected() {
+        let mut cell = sealed([1, 2, 3, 4], 42);
+
+        // A reset in the middle of a write that isn't followed by `seal`
+        unsafe {
+            (*cell.as_mut_ptr())[2] = 5;
+
+            assert_eq!(cell.read(42), None);
+        }
+    }
+
+    #[test]
+    fn other_salt_is_rejected() {
+        let cell = sealed([1, 2, 3, 4], 42);
+
+        assert_eq!(unsafe { cell.read(43) }, None);
+    }
+}
@@ -0,0 +1,194 @@
+use core::{
+    cell::Cell,
+    panic::PanicInfo,
+    ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+/// The frame of the task that's currently running, null before `#[init]` starts
+static CURRENT: AtomicPtr<TaskFrame> = AtomicPtr::new(ptr::null_mut());
+
+/// A task that's running or that was preempted by the running task
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TaskInfo {
+    /// The name of the task, as written in the application
+    pub name: &'static str,
+    /// The priority the task runs at; 0 for `#[init]` and `#[idle]`
+    pub priority: u8,
+}
+
+/// What kind of task a [`TaskMetadata`] describes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskKind {
+    /// The `#[init]` function
+    Init,
+    /// The `#[idle]` function
+    Idle,
+    /// A task bound to an interrupt or exception
+    Hardware,
+    /// A task that's spawned
+    Software,
+}
+
+/// A shared resource a task accesses
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResourceAccess {
+    /// The name of the resource
+    pub name: &'static str,
+    /// Whether the task only gets a shared reference, `shared = [&name]`
+    pub by_ref: bool,
+    /// The priority ceiling of the resource
+    pub ceiling: u8,
+}
+
+/// Compile time description of a task, the `METADATA` constant of its module
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TaskMetadata {
+    /// The name of the task, as written in the application
+    pub name: &'static str,
+    /// The kind of task
+    pub kind: TaskKind,
+    /// The priority the task runs at
+    pub priority: u8,
+    /// The interrupt or exception a hardware task is bound to
+    pub binds: Option<&'static str>,
+    /// How many spawns of a software task can be pending at once, `None` for the other tasks and
+    /// for `capacity = unbounded`
+    pub capacity: Option<u16>,
+    /// The shared resources the task accesses
+    pub shared_resources: &'static [ResourceAccess],
+}
+
+/// The task that's currently running
+///
+/// Returns `None` before `#[init]` runs and, when there's no `#[idle]`, after `#[init]` returns
+/// and no task is running.
+pub fn current_task() -> Option<TaskInfo> {
+    preemption_chain().next()
+}
+
+/// The running task followed by the tasks it preempted, one after the other
+pub fn preemption_chain() -> PreemptionChain {
+    PreemptionChain {
+        frame: CURRENT.load(Ordering::Acquire),
+    }
+}
+
+/// Iterator over the running task and the tasks it preempted, returned by [`preemption_chain`]
+#[derive(Clone)]
+pub struct PreemptionChain {
+    frame: *const TaskFrame,
+}
+
+impl Iterator for PreemptionChain {
+    type Item = TaskInfo;
+
+    fn next(&mut self) -> Option<TaskInfo> {
+        // NOTE(unsafe) a frame is entered and left by the handler that owns it, so the frames of
+        // the tasks below the caller outlive the call
+        let frame = unsafe { self.frame.as_ref()? };
+        self.frame = frame.preempted.get();
+
+        Some(frame.task)
+    }
+}
+
+/// What was known when the application faulted, passed to the `#[fault]` function
+pub struct FaultInfo<'a> {
+    /// The task that was running when the fault happened followed by the tasks it preempted
+    pub chain: PreemptionChain,
+    /// The panic, when the fault is a panic rather than a `HardFault`
+    pub panic: Option<&'a PanicInfo<'a>>,
+    /// Configurable Fault Status Register, 0 on ARMv6-M
+    pub cfsr: u32,
+    /// HardFault Status Register, 0 on ARMv6-M
+    pub hfsr: u32,
+    /// MemManage Fault Address Register, 0 on ARMv6-M
+    pub mmfar: u32,
+    /// BusFault Address Register, 0 on ARMv6-M
+    pub bfar: u32,
+}
+
+impl<'a> FaultInfo<'a> {
+    /// Captures the preemption chain and the fault registers of the System Control Block
+    pub fn capture(panic: Option<&'a PanicInfo<'a>>) -> Self {
+        #[cfg(not(armv6m))]
+        let (cfsr, hfsr, mmfar, bfar) = {
+            // NOTE(unsafe) read-only accesses to registers of the SCB
+            let scb = unsafe { &*cortex_m::peripheral::SCB::PTR };
+
+            (
+                scb.cfsr.read(),
+                scb.hfsr.read(),
+                scb.mmfar.read(),
+                scb.bfar.read(),
+            )
+        };
+
+        // ARMv6-M has no fault status registers
+        #[cfg(armv6m)]
+        let (cfsr, hfsr, mmfar, bfar) = (0, 0, 0, 0);
+
+        FaultInfo {
+            chain: preemption_chain(),
+            panic,
+            cfsr,
+            hfsr,
+            mmfar,
+            bfar,
+        }
+    }
+}
+
+/// A task on the stack of running tasks, kept on the stack of the handler that runs it
+pub struct TaskFrame {
+    task: TaskInfo,
+    preempted: Cell<*const TaskFrame>,
+}
+
+impl TaskFrame {
+    /// A frame for task `name`, not yet entered
+    #[inline(always)]
+    pub fn new(name: &'static str, priority: u8) -> Self {
+        TaskFrame {
+            task: TaskInfo { name, priority },
+            preempted: Cell::new(ptr::null()),
+        }
+    }
+
+    /// Makes this the running task, until `leave` is called
+    ///
+    /// # Safety
+    ///
+    /// The frame must not be moved until it's left, and frames must be left in the reverse order
+    /// they were entered
+    #[inline(always)]
+    pub unsafe fn enter(&self) {
+        self.preempted.set(CURRENT.load(Ordering::Acquire));
+        CURRENT.store(
+            self as *const TaskFrame as *mut TaskFrame,
+            Ordering::Release,
+        );
+    }
+
+    /// Makes the task this one preempted the running task again
+    #[inline(always)]
+    pub fn leave(&self) {
+        CURRENT.store(self.preempted.get() as *mut TaskFrame, Ordering::Release);
+    }
+}
+
+/// Runs `f` as task `name`
+#[inline(always)]
+pub fn with_task<F, R>(name: &'static str, priority: u8, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let frame = TaskFrame::new(name, priority);
+    // NOTE(unsafe) the frame stays in place and is left before returning
+    unsafe { frame.enter() };
+    let r = f();
+    frame.leave();
+
+    r
+}
@@ -14,17 +14,18 @@ fn unwrapper<T, E>(val: Result<T, E>) -> T {
     }
 }
 
-pub struct TimerQueue<Mono, Task, const N: usize>(
-    pub SortedLinkedList<NotReady<Mono, Task>, LinkedIndexU16, Min, N>,
+pub struct TimerQueue<Mono, Task, Index, const N: usize>(
+    pub SortedLinkedList<NotReady<Mono, Task, Index>, LinkedIndexU16, Min, N>,
 )
 where
     Mono: Monotonic,
     Task: Copy;
 
-impl<Mono, Task, const N: usize> TimerQueue<Mono, Task, N>
+impl<Mono, Task, Index, const N: usize> TimerQueue<Mono, Task, Index, N>
 where
     Mono: Monotonic,
     Task: Copy,
+    Index: Copy,
 {
     /// # Safety
     ///
@@ -35,7 +36,7 @@ where
     #[inline]
     pub unsafe fn enqueue_unchecked<F1, F2>(
         &mut self,
-        nr: NotReady<Mono, Task>,
+        nr: NotReady<Mono, Task, Index>,
         enable_interrupt: F1,
         pend_handler: F2,
         mono: Option<&mut Mono>,
@@ -72,7 +73,7 @@ where
     }
 
     /// Cancel the marker value
-    pub fn cancel_marker(&mut self, marker: u32) -> Option<(Task, u8)> {
+    pub fn cancel_marker(&mut self, marker: u32) -> Option<(Task, Index)> {
         if let Some(val) = self.0.find_mut(|nr| nr.marker == marker) {
             let nr = val.pop();
 
@@ -82,6 +83,20 @@ where
         }
     }
 
+    /// Cancel the first entry whose task matches `f`
+    pub fn cancel_task<F>(&mut self, mut f: F) -> Option<(Task, Index)>
+    where
+        F: FnMut(Task) -> bool,
+    {
+        if let Some(val) = self.0.find_mut(|nr| f(nr.task)) {
+            let nr = val.pop();
+
+            Some((nr.task, nr.index))
+        } else {
+            None
+        }
+    }
+
     /// Update the instant at an marker value to a new instant
     pub fn update_marker<F: FnOnce()>(
         &mut self,
@@ -104,7 +119,7 @@ where
     }
 
     /// Dequeue a task from the TimerQueue
-    pub fn dequeue<F>(&mut self, disable_interrupt: F, mono: &mut Mono) -> Option<(Task, u8)>
+    pub fn dequeue<F>(&mut self, disable_interrupt: F, mono: &mut Mono) -> Option<(Task, Index)>
     where
         F: FnOnce(),
     {
@@ -147,27 +162,30 @@ where
     }
 }
 
-pub struct NotReady<Mono, Task>
+pub struct NotReady<Mono, Task, Index>
 where
     Task: Copy,
+    Index: Copy,
     Mono: Monotonic,
 {
-    pub index: u8,
+    pub index: Index,
     pub instant: Instant<Mono>,
     pub task: Task,
     pub marker: u32,
 }
 
-impl<Mono, Task> Eq for NotReady<Mono, Task>
+impl<Mono, Task, Index> Eq for NotReady<Mono, Task, Index>
 where
     Task: Copy,
+    Index: Copy,
     Mono: Monotonic,
 {
 }
 
-impl<Mono, Task> Ord for NotReady<Mono, Task>
+impl<Mono, Task, Index> Ord for NotReady<Mono, Task, Index>
 where
     Task: Copy,
+    Index: Copy,
     Mono: Monotonic,
 {
     fn cmp(&self, other: &Self) -> Ordering {
@@ -175,9 +193,10 @@ where
     }
 }
 
-impl<Mono, Task> PartialEq for NotReady<Mono, Task>
+impl<Mono, Task, Index> PartialEq for NotReady<Mono, Task, Index>
 where
     Task: Copy,
+    Index: Copy,
     Mono: Monotonic,
 {
     fn eq(&self, other: &Self) -> bool {
@@ -185,9 +204,10 @@ where
     }
 }
 
-impl<Mono, Task> PartialOrd for NotReady<Mono, Task>
+impl<Mono, Task, Index> PartialOrd for NotReady<Mono, Task, Index>
 where
     Task: Copy,
+    Index: Copy,
     Mono: Monotonic,
 {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
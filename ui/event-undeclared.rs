@@ -0,0 +1,18 @@
+#![no_main]
+
+#[rtic::app(device = lm3s6965, dispatchers = [UART0])]
+mod app {
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+        (Shared {}, Local {}, init::Monotonics())
+    }
+
+    #[task(subscribe = [Pressed])]
+    fn a(_: a::Context, _: u8) {}
+}
@@ -0,0 +1,28 @@
+#![no_main]
+
+#[rtic::app(device = lm3s6965, dispatchers = [UART0])]
+mod app {
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+        (Shared {}, Local {}, init::Monotonics())
+    }
+
+    #[task(binds = HardFault)]
+    fn hard_fault(_: hard_fault::Context) {
+        foo::call(1, bar::spawn).ok();
+    }
+
+    #[task]
+    fn foo(_: foo::Context, x: u32) -> u32 {
+        x + 1
+    }
+
+    #[task]
+    fn bar(_: bar::Context, _: u32) {}
+}
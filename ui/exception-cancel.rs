@@ -0,0 +1,30 @@
+#![no_main]
+
+#[rtic::app(device = lm3s6965, dispatchers = [UART0])]
+mod app {
+    use systick_monotonic::Systick;
+
+    #[monotonic(binds = SysTick, default = true)]
+    type MyMono = Systick<100>;
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+        (Shared {}, Local {}, init::Monotonics())
+    }
+
+    #[task(binds = HardFault, local = [handle: Option<foo::SpawnHandle> = None])]
+    fn hard_fault(cx: hard_fault::Context) {
+        if let Some(handle) = cx.local.handle.take() {
+            foo::SpawnHandle::cancel(handle).ok();
+        }
+    }
+
+    #[task]
+    fn foo(_: foo::Context) {}
+}
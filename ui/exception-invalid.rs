@@ -3,16 +3,18 @@
 #[rtic::app(device = lm3s6965)]
 mod app {
     #[shared]
-    struct Shared {}
+    struct Shared {
+        counter: u32,
+    }
 
     #[local]
     struct Local {}
 
     #[init]
     fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
-        (Shared {}, Local {}, init::Monotonics())
+        (Shared { counter: 0 }, Local {}, init::Monotonics())
     }
 
-    #[task(binds = NonMaskableInt)]
+    #[task(binds = NonMaskableInt, shared = [counter])]
     fn nmi(_: nmi::Context) {}
 }
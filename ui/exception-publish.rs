@@ -0,0 +1,26 @@
+#![no_main]
+
+#[rtic::app(device = lm3s6965, dispatchers = [UART0])]
+mod app {
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[event]
+    type Tick = u32;
+
+    #[init]
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+        (Shared {}, Local {}, init::Monotonics())
+    }
+
+    #[task(binds = HardFault)]
+    fn hard_fault(_: hard_fault::Context) {
+        Tick::publish(1).ok();
+    }
+
+    #[task(subscribe = [Tick])]
+    fn foo(_: foo::Context, _: u32) {}
+}
@@ -0,0 +1,20 @@
+#![no_main]
+
+#[rtic::app(device = lm3s6965, dispatchers = [UART0], modes = [Normal, Safe])]
+mod app {
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+        (Shared {}, Local {}, init::Monotonics())
+    }
+
+    #[task(binds = HardFault)]
+    fn hard_fault(_: hard_fault::Context) {
+        set_mode(Mode::Safe);
+    }
+}
@@ -0,0 +1,23 @@
+#![no_main]
+
+#[rtic::app(device = lm3s6965, dispatchers = [UART0])]
+mod app {
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+        (Shared {}, Local {}, init::Monotonics())
+    }
+
+    #[task(binds = HardFault)]
+    fn hard_fault(_: hard_fault::Context) {
+        foo::spawn_with(|slot| slot.write([0; 64])).ok();
+    }
+
+    #[task]
+    fn foo(_: foo::Context, _: [u8; 64]) {}
+}
@@ -0,0 +1,23 @@
+#![no_main]
+
+#[rtic::app(device = lm3s6965)]
+mod app {
+    use rtic::FaultInfo;
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(_: init::Context) -> (Shared, Local, init::Monotonics) {
+        (Shared {}, Local {}, init::Monotonics())
+    }
+
+    #[task(binds = HardFault)]
+    fn hard_fault(_: hard_fault::Context) {}
+
+    #[fault]
+    fn on_fault(_: FaultInfo) {}
+}
@@ -0,0 +1,21 @@
+#![no_main]
+
+#[rtic::app(device = lm3s6965)]
+mod app {
+    #[shared]
+    struct Shared {
+        #[frozen]
+        calibration: u32,
+    }
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(_: init::Context) -> (Shared, Local, init::Monotonics) {
+        (Shared { calibration: 0 }, Local {}, init::Monotonics())
+    }
+
+    #[task(binds = UART0, shared = [calibration])]
+    fn uart0(_: uart0::Context) {}
+}
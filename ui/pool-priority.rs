@@ -0,0 +1,21 @@
+#![no_main]
+
+#[rtic::app(device = lm3s6965, dispatchers = [SSI0, QEI0], pools = [messages = 2])]
+mod app {
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(_: init::Context) -> (Shared, Local, init::Monotonics) {
+        (Shared {}, Local {}, init::Monotonics())
+    }
+
+    #[task(pool = messages)]
+    fn bar(_: bar::Context, _: u32) {}
+
+    #[task(priority = 2, pool = messages)]
+    fn foo(_: foo::Context, _: u32) {}
+}
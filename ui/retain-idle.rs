@@ -0,0 +1,25 @@
+#![no_main]
+
+#[rtic::app(device = lm3s6965)]
+mod app {
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {
+        #[retain]
+        boots: u32,
+    }
+
+    #[init]
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+        let boots = cx.retained.boots.unwrap_or(0) + 1;
+
+        (Shared {}, Local { boots }, init::Monotonics())
+    }
+
+    #[idle(local = [boots])]
+    fn idle(_: idle::Context) -> ! {
+        loop {}
+    }
+}
@@ -0,0 +1,21 @@
+#![no_main]
+
+#[rtic::app(device = lm3s6965)]
+mod app {
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {
+        #[retain]
+        #[init(in_place)]
+        samples: [u32; 256],
+    }
+
+    #[init]
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+        let samples = unsafe { rtic::InPlace::assume_init(cx.uninit.samples) };
+
+        (Shared {}, Local { samples }, init::Monotonics())
+    }
+}
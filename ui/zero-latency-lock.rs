@@ -0,0 +1,23 @@
+#![no_main]
+
+#[rtic::app(device = lm3s6965)]
+mod app {
+    #[shared]
+    struct Shared {
+        shared: u32,
+    }
+
+    #[local]
+    struct Local {}
+
+    #[init]
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+        (Shared { shared: 0 }, Local {}, init::Monotonics())
+    }
+
+    #[task(binds = UART0, shared = [shared])]
+    fn foo(_: foo::Context) {}
+
+    #[task(binds = GPIOA, zero_latency, shared = [shared])]
+    fn bar(_: bar::Context) {}
+}
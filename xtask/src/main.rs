@@ -138,12 +138,34 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+// the features an example requires, see the `[[example]]` sections of `Cargo.toml`
+fn example_features(example: &str) -> Option<&'static str> {
+    match example {
+        "unbounded" => Some("alloc"),
+        _ => None,
+    }
+}
+
+// whether `example` can be built for `target`
+fn example_supported(example: &str, target: &str) -> bool {
+    match example {
+        // zero latency tasks need BASEPRI
+        "zero-latency" => target != ARMV6M,
+        _ => true,
+    }
+}
+
 fn run_test(target: &str, examples: &[String]) -> anyhow::Result<()> {
     for example in examples {
+        if !example_supported(example, target) {
+            continue;
+        }
+
+        let features = example_features(example);
         let cmd = CargoCommand::Run {
             example,
             target,
-            features: None,
+            features,
             mode: BuildMode::Release,
         };
 
@@ -153,7 +175,7 @@ fn run_test(target: &str, examples: &[String]) -> anyhow::Result<()> {
             &CargoCommand::Build {
                 example,
                 target,
-                features: None,
+                features,
                 mode: BuildMode::Release,
             },
             1,
@@ -220,18 +242,22 @@ fn build_test(target: &str, examples: &[String]) -> anyhow::Result<()> {
     let build_path: PathBuf = ["target", target, "release", "examples"].iter().collect();
 
     for example in examples {
-        let no_features = None;
+        if !example_supported(example, target) {
+            continue;
+        }
+
+        let features = example_features(example);
         arm_example(
             &CargoCommand::Build {
                 target,
                 example,
                 mode: BuildMode::Release,
-                features: no_features,
+                features,
             },
             2,
         )?;
-        let expected = build_hexpath(example, no_features, BuildMode::Release, 1)?;
-        let got = build_hexpath(example, no_features, BuildMode::Release, 2)?;
+        let expected = build_hexpath(example, features, BuildMode::Release, 1)?;
+        let got = build_hexpath(example, features, BuildMode::Release, 2)?;
 
         compare_builds(expected, got)?;
 
@@ -240,12 +266,12 @@ fn build_test(target: &str, examples: &[String]) -> anyhow::Result<()> {
                 target,
                 example,
                 mode: BuildMode::Release,
-                features: no_features,
+                features,
             },
             2,
         )?;
-        let expected = build_hexpath(example, no_features, BuildMode::Release, 1)?;
-        let got = build_hexpath(example, no_features, BuildMode::Release, 2)?;
+        let expected = build_hexpath(example, features, BuildMode::Release, 1)?;
+        let got = build_hexpath(example, features, BuildMode::Release, 2)?;
 
         compare_builds(expected, got)?;
 